@@ -1,8 +1,8 @@
 extern crate squid;
 
 use squid::BlockParser;
-use squid::ast::{HeadingLevel, Block};
-use squid::html::{Renderer, Format};
+use squid::ast::{HeadingLevel, Block, Text};
+use squid::html::{Renderer, Format, RenderContext};
 use squid::html::builders::Builder;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -11,19 +11,32 @@ use std::io::{BufRead, BufReader};
 struct CustomFormat;
 
 impl Format for CustomFormat {
-    fn heading(&self, builder: &mut Builder, level: HeadingLevel, content: String) {
+    fn heading(
+        &self,
+        builder: &mut Builder,
+        context: &mut RenderContext,
+        level: HeadingLevel,
+        content: Text,
+        _number: Option<Vec<usize>>,
+        _label: Option<String>,
+    ) {
         let level_str = match level {
             HeadingLevel::Level2 => "2",
             HeadingLevel::Level3 => "3",
+            HeadingLevel::Level4 => "4",
+            HeadingLevel::Level5 => "5",
+            HeadingLevel::Level6 => "6",
             _ => "1",
         };
 
         builder
             .tag_start("div")
             .add_attr("class", format!("heading-level-{}", level_str))
-            .finish()
-            .text(content)
-            .tag_end("div");
+            .finish();
+
+        self.text(builder, context, content);
+
+        builder.tag_end("div");
     }
 }
 
@@ -1,36 +1,171 @@
-use super::text::Text;
+use super::text::{Inline, Text};
+use std::ops::Range;
 
 pub type Document = Vec<Block>;
 
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
 #[non_exhaustive]
 pub enum HeadingLevel {
     Level1,
     Level2,
     Level3,
+    Level4,
+    Level5,
+    Level6,
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[non_exhaustive]
+pub enum NumberingStyle {
+    /// 1, 2, 3, ...
+    Decimal,
+    /// a, b, c, ...
+    LowerAlpha,
+    /// A, B, C, ...
+    UpperAlpha,
+    /// i, ii, iii, ...
+    LowerRoman,
+    /// I, II, III, ...
+    UpperRoman,
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 #[non_exhaustive]
 pub enum ListType {
     Unordered,
-    Ordered,
+    /// Contains the number of the first item and the numbering style.
+    Ordered { start: u32, style: NumberingStyle },
 }
 
 #[derive(Debug, Eq, PartialEq)]
 #[non_exhaustive]
 pub enum Decorator {
-    /// Decorator for a table block
-    Table,
     /// Decorator for a code block. Contains the language name.
     Code(Option<String>),
+    /// Decorator for an ordered list. Contains the numbering style.
+    List(NumberingStyle),
+    /// Decorator for a math block
+    Math,
+    /// Decorator excluding a heading from numbering
+    Unnumbered,
+    /// Decorator labeling a heading or figure for cross-references. Contains the label.
+    Label(String),
+    /// Decorator adding a caption to the following block. Contains the caption.
+    Figure(String),
+    /// Decorator replaced by the blocks of another file. Contains the path of the file.
+    Include(String),
+    /// Decorator for a block that is only parsed if a flag is set. Contains the flag.
+    If(String),
+    /// Decorator for a block that is only parsed if the flag of the preceding `If` is not set
+    Else,
+}
+
+///
+/// Formats a hierarchical number, e.g. `1.2` for `[1, 2]`.
+///
+pub fn format_number(number: &[usize]) -> String {
+    number
+        .iter()
+        .map(usize::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+///
+/// The location of a part of a line in the source.
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Span {
+    /// The included file the line belongs to, `None` for the input itself
+    pub file: Option<String>,
+    /// The number of the line, starting at 1
+    pub line: usize,
+    /// The byte offsets of the part within the line
+    pub range: Range<usize>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct ListItem {
+    /// `Some` if the item starts with a checkbox (`[ ]` or `[x]`),
+    /// containing whether the checkbox is checked.
+    pub checked: Option<bool>,
+    /// The location of the checkbox in the source, if the item has one
+    pub checkbox_span: Option<Span>,
+    /// The explicit number of an ordered list item (e.g. `3. item`)
+    pub number: Option<u32>,
+    pub content: Text,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Footnote {
+    /// Footnotes are numbered in the order they first appear (referenced or defined),
+    /// starting at 1.
+    pub number: usize,
+    pub label: String,
+    pub content: Text,
+    /// Whether the footnote is referenced anywhere in the document
+    pub referenced: bool,
 }
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum Block {
-    Heading(HeadingLevel, String),
+    Heading {
+        level: HeadingLevel,
+        content: Text,
+        /// The number of the heading within its parents (e.g. `[1, 2]` for 1.2),
+        /// if heading numbering is enabled and the heading isn't `[unnumbered]`.
+        number: Option<Vec<usize>>,
+        /// The label cross-references use to refer to the heading (`[label <name>]`)
+        label: Option<String>,
+    },
     Paragraph(Text),
     Quote(Text),
     Preformatted(Option<Decorator>, String),
-    List(ListType, Vec<Text>),
+    List(ListType, Vec<ListItem>),
+    FootnoteDefinition(Footnote),
+    /// Contains the terms and their definitions.
+    DefinitionList(Vec<(Text, Vec<Block>)>),
+    /// Contains the TeX source of a display formula.
+    Math(String),
+    /// A block with a caption (`[figure <caption>]`)
+    Figure {
+        caption: Text,
+        body: Box<Block>,
+        /// The number of the figure among the figures of the same `FigureKind`,
+        /// unless the figure is `[unnumbered]`
+        number: Option<usize>,
+        label: Option<String>,
+    },
+    /// Contains the text of a line (`// …`) or block (`/* … */`) comment.
+    /// Comments are only kept if enabled on the `BlockParser`, and are never rendered.
+    Comment(String),
+}
+
+///
+/// Figures are numbered separately for each kind of content.
+///
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[non_exhaustive]
+pub enum FigureKind {
+    /// A paragraph containing only an image
+    Image,
+    Code,
+    Math,
+    Other,
+}
+
+impl FigureKind {
+    pub fn of(body: &Block) -> Self {
+        match *body {
+            Block::Paragraph(ref text) if text.len() == 1 => {
+                match text[0] {
+                    Inline::Image { .. } => FigureKind::Image,
+                    _ => FigureKind::Other,
+                }
+            }
+            Block::Preformatted(Some(Decorator::Code(_)), _) => FigureKind::Code,
+            Block::Math(_) => FigureKind::Math,
+            _ => FigureKind::Other,
+        }
+    }
 }
@@ -4,4 +4,78 @@ pub type Text = Vec<Inline>;
 pub enum Inline {
     LineBreak,
     Chunk(String),
+    /// A link. The target is `None` while the definition of a reference link (`[text][label]`)
+    /// hasn't been seen yet. References that are never defined are turned into text,
+    /// so parsed blocks always contain the target.
+    Link { target: Option<String>, content: Text },
+    /// An image. Its source is `None` while it is unresolved, like the target of a link.
+    Image { source: Option<String>, alt: String },
+    /// A reference to a footnote (`[^label]`).
+    /// `occurrence` counts the references to the same footnote, starting at 1.
+    FootnoteRef {
+        label: String,
+        number: usize,
+        occurrence: usize,
+    },
+    /// Contains the TeX source of an inline formula (`$x^2$`).
+    Math(String),
+    /// A reference to a labeled heading or figure (`[@label]`). Contains the label and the text
+    /// the reference is rendered as: the number of the target, or its title if it has none.
+    /// The text is `None` while the label hasn't been seen yet. References to labels that are
+    /// never defined are turned into text, so parsed blocks always contain the text.
+    CrossRef { label: String, text: Option<String> },
+}
+
+///
+/// Extracts the text content without any markup,
+/// e.g. for tables of contents, slugs or document titles.
+///
+/// # Example
+///
+/// ```
+/// use squid::ast::{Inline, PlainText};
+///
+/// let text = vec![
+///     Inline::Chunk("Hello".into()),
+///     Inline::LineBreak,
+///     Inline::Chunk("World".into()),
+/// ];
+///
+/// assert_eq!("Hello World", text.plain_text());
+/// ```
+///
+pub trait PlainText {
+    fn plain_text(&self) -> String {
+        let mut buffer = String::new();
+
+        self.push_plain_text(&mut buffer);
+
+        buffer
+    }
+
+    fn push_plain_text(&self, buffer: &mut String);
+}
+
+impl PlainText for Inline {
+    fn push_plain_text(&self, buffer: &mut String) {
+        match *self {
+            Inline::LineBreak => buffer.push(' '),
+            Inline::Chunk(ref text) => buffer.push_str(text),
+            Inline::Link { ref content, .. } => content.push_plain_text(buffer),
+            Inline::Image { ref alt, .. } => buffer.push_str(alt),
+            Inline::FootnoteRef { .. } => {}
+            Inline::Math(ref source) => buffer.push_str(source),
+            Inline::CrossRef { ref label, ref text } => {
+                buffer.push_str(text.as_ref().unwrap_or(label))
+            }
+        }
+    }
+}
+
+impl PlainText for [Inline] {
+    fn push_plain_text(&self, buffer: &mut String) {
+        for inline in self {
+            inline.push_plain_text(buffer);
+        }
+    }
 }
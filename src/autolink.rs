@@ -0,0 +1,234 @@
+use super::ast::{Inline, Text};
+
+const URL_SCHEMES: &[&str] = &["https://", "http://"];
+const EMAIL_SCHEME: &str = "mailto:";
+const EMAIL_SEPARATOR: char = '@';
+
+///
+/// Characters that are not considered part of a URL or email address when they appear at its end,
+/// e.g. the period in `see https://squid.rs.`
+///
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ':', ';', '!', '?', '\'', '"', '*', '_', '>'];
+
+fn is_email_local_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || ".!#$%&'*+/=?^_`{|}~-".contains(c)
+}
+
+fn is_email_domain_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.' || c == '-'
+}
+
+fn trim_url_end(url: &str) -> &str {
+    let mut url = url;
+
+    loop {
+        let mut trimmed = url.trim_end_matches(TRAILING_PUNCTUATION);
+
+        // A closing parenthesis only belongs to the URL if it is balanced
+        // e.g. `https://en.wikipedia.org/wiki/Squid_(disambiguation)`
+        if trimmed.ends_with(')') && trimmed.matches(')').count() > trimmed.matches('(').count() {
+            trimmed = &trimmed[..trimmed.len() - 1];
+        }
+
+        if trimmed.len() == url.len() {
+            return url;
+        }
+
+        url = trimmed;
+    }
+}
+
+///
+/// Returns the start and end of the first URL in `word`.
+///
+fn find_url(word: &str) -> Option<(usize, usize)> {
+    URL_SCHEMES
+        .iter()
+        .filter_map(|scheme| {
+            let start = word.find(scheme)?;
+            let preceding = word[..start].chars().next_back();
+            let preceded_by_word = preceding.is_some_and(char::is_alphanumeric);
+            let url = trim_url_end(&word[start..]);
+
+            if preceded_by_word || url.len() == scheme.len() {
+                None
+            } else {
+                Some((start, start + url.len()))
+            }
+        })
+        .min()
+}
+
+///
+/// Returns the start and end of the email address in `word`.
+///
+fn find_email(word: &str) -> Option<(usize, usize)> {
+    let separator = word.find(EMAIL_SEPARATOR)?;
+    let local = word[..separator].trim_start_matches(|c| !is_email_local_char(c));
+    let start = separator - local.len();
+
+    if local.is_empty() || !local.chars().all(is_email_local_char) {
+        return None;
+    }
+
+    let domain_start = separator + 1;
+    let domain = &word[domain_start..];
+    let domain_length = domain.find(|c| !is_email_domain_char(c)).unwrap_or(domain.len());
+    let domain = domain[..domain_length].trim_end_matches(['.', '-']);
+
+    let mut labels = domain.split('.');
+    let is_valid_label = |label: &str| !label.is_empty() && !label.starts_with('-');
+    let top_level = labels.next_back()?;
+
+    if domain.contains('.') && labels.all(is_valid_label) && top_level.len() >= 2 &&
+        top_level.chars().all(|c| c.is_ascii_alphabetic())
+    {
+        Some((start, domain_start + domain.len()))
+    } else {
+        None
+    }
+}
+
+fn find_link(word: &str) -> Option<(usize, usize, Inline)> {
+    if let Some((start, end)) = find_url(word) {
+        let url = &word[start..end];
+
+        return Some((
+            start,
+            end,
+            Inline::Link {
+                target: Some(url.into()),
+                content: vec![Inline::Chunk(url.into())],
+            },
+        ));
+    }
+
+    let (start, end) = find_email(word)?;
+    let email = &word[start..end];
+
+    Some((
+        start,
+        end,
+        Inline::Link {
+            target: Some(format!("{}{}", EMAIL_SCHEME, email)),
+            content: vec![Inline::Chunk(email.into())],
+        },
+    ))
+}
+
+fn autolink_chunk(chunk: String, text: &mut Text) {
+    let mut plain = String::new();
+    let mut rest = chunk.as_str();
+
+    while !rest.is_empty() {
+        let word_start = rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len());
+        let word_end = rest[word_start..]
+            .find(char::is_whitespace)
+            .map_or(rest.len(), |end| word_start + end);
+
+        match find_link(&rest[word_start..word_end]) {
+            Some((start, end, link)) => {
+                plain.push_str(&rest[..word_start + start]);
+
+                if !plain.is_empty() {
+                    text.push(Inline::Chunk(plain));
+                    plain = String::new();
+                }
+
+                text.push(link);
+                rest = &rest[word_start + end..];
+            }
+            None => {
+                plain.push_str(&rest[..word_end]);
+                rest = &rest[word_end..];
+            }
+        }
+    }
+
+    if !plain.is_empty() {
+        text.push(Inline::Chunk(plain));
+    }
+}
+
+///
+/// Turns URLs and email addresses in the chunks of `text` into links.
+/// Only the top level is processed, as links cannot contain other links.
+///
+pub fn autolink(text: Text) -> Text {
+    let mut output = Vec::with_capacity(text.len());
+
+    for inline in text {
+        match inline {
+            Inline::Chunk(chunk) => autolink_chunk(chunk, &mut output),
+            inline => output.push(inline),
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(target: &str, content: &str) -> Inline {
+        Inline::Link {
+            target: Some(target.into()),
+            content: vec![Inline::Chunk(content.into())],
+        }
+    }
+
+    #[test]
+    fn urls_work() {
+        assert_eq!(
+            vec![
+                Inline::Chunk("see ".into()),
+                link("https://squid.rs/docs?a=1", "https://squid.rs/docs?a=1"),
+                Inline::Chunk(", (".into()),
+                link(
+                    "https://en.wikipedia.org/wiki/Squid_(disambiguation)",
+                    "https://en.wikipedia.org/wiki/Squid_(disambiguation)",
+                ),
+                Inline::Chunk(") and <".into()),
+                link("http://example.com", "http://example.com"),
+                Inline::Chunk(">.".into()),
+            ],
+            autolink(vec![
+                Inline::Chunk(
+                    "see https://squid.rs/docs?a=1, \
+                     (https://en.wikipedia.org/wiki/Squid_(disambiguation)) \
+                     and <http://example.com>."
+                        .into(),
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn emails_work() {
+        assert_eq!(
+            vec![
+                Inline::Chunk("mail ".into()),
+                link("mailto:jane.doe+squid@example.co.uk", "jane.doe+squid@example.co.uk"),
+                Inline::Chunk(".".into()),
+            ],
+            autolink(vec![Inline::Chunk("mail jane.doe+squid@example.co.uk.".into())])
+        );
+    }
+
+    #[test]
+    fn non_links_are_kept() {
+        let text = vec![
+            Inline::Chunk("https:// nohttps://squid.rs @squid foo@bar foo@bar.1 ".into()),
+            link("https://squid.rs", "squid"),
+        ];
+
+        assert_eq!(
+            vec![
+                Inline::Chunk("https:// nohttps://squid.rs @squid foo@bar foo@bar.1 ".into()),
+                link("https://squid.rs", "squid"),
+            ],
+            autolink(text)
+        );
+    }
+}
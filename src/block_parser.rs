@@ -1,8 +1,14 @@
-use super::block_tokenizer::BlockTokenizer;
-use super::tokens::LineType;
-use super::ast::{Block, HeadingLevel, Inline, Text};
+use super::block_tokenizer::{self, BlockTokenizer};
+use super::tokens::{Line, LineType};
+use super::ast::{self, Block, Decorator, FigureKind, Footnote, HeadingLevel, ListItem, ListType,
+                 NumberingStyle, PlainText, Span, Text};
 use super::input::IntoParserInput;
-use super::error::ParseError;
+use super::inline_parser::{self, InlineParser, LineOffset};
+use super::pending::PendingBlocks;
+use super::error::{Diagnostic, DiagnosticKind, ParseError};
+use super::resolver::Resolver;
+use super::constants;
+use std::collections::{HashMap, HashSet};
 use std::str::Lines;
 
 macro_rules! consume_error {
@@ -23,16 +29,60 @@ where
     I: Iterator<Item = S>,
 {
     tokenizer: BlockTokenizer<'a, S, I>,
+    inline_parser: InlineParser,
+    pending: PendingBlocks,
+    diagnostics: Vec<Diagnostic>,
+    /// The numbers of the last heading of each level, if headings are numbered
+    heading_numbers: Option<Vec<usize>>,
+    /// The number of figures of each kind
+    figure_numbers: HashMap<FigureKind, usize>,
+    resolver: Option<Box<dyn Resolver>>,
+    /// The path of the document itself, if known
+    path: Option<String>,
+    /// The name the resolver returns for `path`, once an include needs it
+    name: Option<String>,
+    /// Variables passed to the parser, which take precedence over the front matter
+    variables: HashMap<String, String>,
+    /// The flags `[if]` blocks are keyed on
+    flags: HashSet<String>,
+    /// The position of the first line of the block that is being parsed,
+    /// which tells the comments before the block from the comments inside of or after it
+    block_start: Option<usize>,
+    /// Whether anything besides blank lines and comments has been parsed,
+    /// after which a divider no longer starts the front matter
+    seen_block: bool,
+    /// Whether the end of the input has been handled, so it's only reported once
+    finished: bool,
+}
+
+///
+/// Decorators that annotate the following block (e.g. `[label intro]`) instead of
+/// changing how it is parsed. They can be combined with each other and other decorators.
+///
+#[derive(Debug)]
+struct Annotations {
+    label: Option<String>,
+    numbered: bool,
+}
+
+impl Default for Annotations {
+    fn default() -> Self {
+        Annotations {
+            label: None,
+            numbered: true,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct TextAccumulator {
     buffer: String,
+    lines: Vec<LineOffset>,
 }
 
 impl<'a> BlockParser<'a, &'a str, Lines<'a>> {
     pub fn from_string(input: &'a str) -> Self {
-        BlockParser { tokenizer: BlockTokenizer::from_string(input) }
+        BlockParser::from_tokenizer(BlockTokenizer::from_string(input))
     }
 }
 
@@ -42,26 +92,307 @@ where
     I: Iterator<Item = S>,
 {
     pub fn new(input: I) -> Self {
-        BlockParser { tokenizer: BlockTokenizer::new(input) }
+        BlockParser::from_tokenizer(BlockTokenizer::new(input))
+    }
+
+    fn from_tokenizer(tokenizer: BlockTokenizer<'a, S, I>) -> Self {
+        BlockParser {
+            tokenizer,
+            inline_parser: InlineParser::new(),
+            pending: PendingBlocks::new(),
+            diagnostics: Vec::new(),
+            heading_numbers: None,
+            figure_numbers: HashMap::new(),
+            resolver: None,
+            path: None,
+            name: None,
+            variables: HashMap::new(),
+            flags: HashSet::new(),
+            block_start: None,
+            seen_block: false,
+            finished: false,
+        }
+    }
+
+    ///
+    /// Enables turning URLs (e.g. `https://squid.rs`) and email addresses in text into links.
+    /// Autolinking is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use squid::BlockParser;
+    ///
+    /// let parser = BlockParser::from_string("Visit https://squid.rs.").autolink(true);
+    /// ```
+    ///
+    pub fn autolink(mut self, enabled: bool) -> Self {
+        self.inline_parser.set_autolink(enabled);
+
+        self
+    }
+
+    ///
+    /// Enables numbering headings by their level (e.g. 1, 1.1, 1.2, 2).
+    /// Headings decorated with `[unnumbered]` are skipped. Numbering is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use squid::BlockParser;
+    /// use squid::ast::Block;
+    ///
+    /// let mut parser = BlockParser::from_string("# Squid\n\n## Ink").number_headings(true);
+    ///
+    /// parser.next();
+    ///
+    /// match parser.next() {
+    ///     Some(Ok(Block::Heading { number, .. })) => assert_eq!(Some(vec![1, 1]), number),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    ///
+    pub fn number_headings(mut self, enabled: bool) -> Self {
+        self.heading_numbers = if enabled { Some(Vec::new()) } else { None };
+
+        self
+    }
+
+    ///
+    /// Defines variables, whose placeholders (e.g. `{{version}}`) are substituted with their
+    /// values. Variables can also be defined in the front matter, a fenced block
+    /// of `name: value` lines at the start of the document. Variables defined here take
+    /// precedence over the front matter.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use squid::BlockParser;
+    /// use squid::ast::{Block, Inline};
+    ///
+    /// let input = "---\nproduct: Squid\n---\n{{product}} {{version}}";
+    /// let mut parser = BlockParser::from_string(input).variables(vec![("version", "1.0")]);
+    ///
+    /// assert_eq!(
+    ///     Block::Paragraph(vec![Inline::Chunk("Squid 1.0".into())]),
+    ///     parser.next().unwrap().unwrap()
+    /// );
+    /// ```
+    ///
+    pub fn variables<V, N, T>(mut self, variables: V) -> Self
+    where
+        V: IntoIterator<Item = (N, T)>,
+        N: Into<String>,
+        T: Into<String>,
+    {
+        for (name, value) in variables {
+            let (name, value) = (name.into(), value.into());
+
+            self.inline_parser.define_variable(&name, &value);
+            self.variables.insert(name, value);
+        }
+
+        self
+    }
+
+    ///
+    /// Leaves placeholders (e.g. `{{version}}`) as they are instead of substituting them,
+    /// e.g. to substitute them after rendering. Placeholders are substituted by default.
+    ///
+    pub fn keep_placeholders(mut self, enabled: bool) -> Self {
+        self.inline_parser.set_keep_placeholders(enabled);
+
+        self
+    }
+
+    ///
+    /// Sets flags, which decide whether the fenced blocks decorated with `[if <flag>]`
+    /// or the following blocks decorated with `[else]` are parsed. Nested blocks use a longer
+    /// divider, like other fenced blocks.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use squid::BlockParser;
+    /// use squid::ast::{Block, Inline};
+    ///
+    /// let input = "[if internal]\n---\nInternal\n---\n[else]\n---\nPublic\n---";
+    /// let mut parser = BlockParser::from_string(input).flags(vec!["internal"]);
+    ///
+    /// assert_eq!(
+    ///     Block::Paragraph(vec![Inline::Chunk("Internal".into())]),
+    ///     parser.next().unwrap().unwrap()
+    /// );
+    /// assert!(parser.next().is_none());
+    /// ```
+    ///
+    pub fn flags<F, T>(mut self, flags: F) -> Self
+    where
+        F: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.flags.extend(flags.into_iter().map(Into::into));
+
+        self
+    }
+
+    ///
+    /// Keeps comments (`// …` and `/* … */`) as `Block::Comment`s instead of dropping them.
+    /// Comments don't interrupt other blocks, so comments inside of a block follow it.
+    /// Comments are dropped by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use squid::BlockParser;
+    /// use squid::ast::Block;
+    ///
+    /// let mut parser = BlockParser::from_string("// TODO\nSquid").keep_comments(true);
+    ///
+    /// assert_eq!(Block::Comment("TODO".into()), parser.next().unwrap().unwrap());
+    /// ```
+    ///
+    pub fn keep_comments(mut self, enabled: bool) -> Self {
+        self.tokenizer.set_keep_comments(enabled);
+
+        self
+    }
+
+    ///
+    /// Sets the resolver that loads the files included with `[include <path>]`.
+    /// Without a resolver, includes fail with a diagnostic.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use squid::BlockParser;
+    /// use squid::ast::Block;
+    /// use squid::resolver::MemoryResolver;
+    ///
+    /// let resolver = MemoryResolver::new().file("intro.sq", "# Intro");
+    /// let mut parser = BlockParser::from_string("[include intro.sq]").resolver(resolver);
+    ///
+    /// match parser.next() {
+    ///     Some(Ok(Block::Heading { .. })) => (),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    ///
+    pub fn resolver<R>(mut self, resolver: R) -> Self
+    where
+        R: Resolver + 'static,
+    {
+        self.resolver = Some(Box::new(resolver));
+
+        self
+    }
+
+    ///
+    /// Sets the path of the document itself, which the resolver resolves like an include.
+    /// Files including the document are then reported as cycles,
+    /// and the paths of its includes are relative to it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use squid::BlockParser;
+    /// use squid::resolver::MemoryResolver;
+    ///
+    /// let resolver = MemoryResolver::new().file("squid.sq", "[include squid.sq]");
+    /// let mut parser = BlockParser::from_string("[include squid.sq]")
+    ///     .resolver(resolver)
+    ///     .path("squid.sq");
+    ///
+    /// assert!(parser.next().is_none());
+    /// assert_eq!(1, parser.diagnostics().len());
+    /// ```
+    ///
+    pub fn path<P>(mut self, path: P) -> Self
+    where
+        P: Into<String>,
+    {
+        self.path = Some(path.into());
+
+        self
+    }
+
+    ///
+    /// Returns the diagnostics collected for the blocks that have been parsed so far.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use squid::BlockParser;
+    ///
+    /// let mut parser = BlockParser::from_string("####### too deep");
+    ///
+    /// for _ in parser.by_ref() {}
+    ///
+    /// for diagnostic in parser.diagnostics() {
+    ///     println!("{}", diagnostic);
+    /// }
+    /// ```
+    ///
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    fn diagnose(&mut self, kind: DiagnosticKind) {
+        let line = self.tokenizer.line();
+
+        self.diagnose_at(line, kind);
+    }
+
+    ///
+    /// Adds a diagnostic for the line at `position`, which may be part of an included file.
+    ///
+    fn diagnose_at(&mut self, position: usize, kind: DiagnosticKind) {
+        let (file, line) = self.tokenizer.locate(position);
+        let diagnostic = Diagnostic::new(line, kind).in_file(file.map(Into::into));
+
+        self.diagnostics.push(diagnostic);
     }
 
     fn parse_text(&mut self) -> Option<Result<Block, ParseError>> {
         let mut accumulator = TextAccumulator::new();
 
+        if let Err(err) = self.accumulate_text(&mut accumulator) {
+            return Some(Err(err));
+        }
+
+        // Text directly followed by a definition is the first term of a definition list
+        if let Some(Ok(LineType::Definition)) = self.tokenizer.peek() {
+            return self.parse_definition_list(accumulator);
+        }
+
+        Some(Ok(Block::Paragraph(accumulator.consume(&mut self.inline_parser))))
+    }
+
+    fn accumulate_text(&mut self, accumulator: &mut TextAccumulator) -> Result<(), ParseError> {
         loop {
             match self.tokenizer.peek() {
-                Some(Err(..)) => consume_error!(self.tokenizer),
+                Some(Err(..)) => {
+                    return match self.tokenizer.consume_raw() {
+                        Some(Err(err)) => Err(err),
+                        // If peek() returns an error, we know that
+                        // consume_raw() must return an error too.
+                        _ => unreachable!(),
+                    };
+                }
                 Some(Ok(LineType::Text)) => {
                     // unwrapping here is safe
                     let line = self.tokenizer.consume(LineType::Text).unwrap().unwrap();
+                    let value = line.value().unwrap();
 
-                    accumulator.add(&line.value().unwrap());
+                    if let Some(level) = block_tokenizer::excessive_heading_level(&value) {
+                        self.diagnose(DiagnosticKind::HeadingLevelTooDeep(level));
+                    }
+
+                    accumulator.add(&value, self.tokenizer.line());
                 }
-                _ => break,
+                _ => return Ok(()),
             }
         }
-
-        Some(Ok(Block::Paragraph(accumulator.consume())))
     }
 
     fn parse_quote(&mut self) -> Option<Result<Block, ParseError>> {
@@ -74,148 +405,2036 @@ where
                     // unwrapping here is safe
                     let line = self.tokenizer.consume(LineType::Quote).unwrap().unwrap();
 
-                    accumulator.add(&line.value().unwrap());
+                    accumulator.add(&line.value().unwrap(), self.tokenizer.line());
                 }
                 _ => break,
             }
         }
 
-        Some(Ok(Block::Quote(accumulator.consume())))
+        Some(Ok(Block::Quote(accumulator.consume(&mut self.inline_parser))))
     }
 
-    fn parse_heading(&mut self, line_type: LineType) -> Option<Result<Block, ParseError>> {
+    fn parse_heading(
+        &mut self,
+        line_type: LineType,
+        annotations: Annotations,
+    ) -> Option<Result<Block, ParseError>> {
         let level = match line_type {
             LineType::Heading1 => HeadingLevel::Level1,
             LineType::Heading2 => HeadingLevel::Level2,
             LineType::Heading3 => HeadingLevel::Level3,
+            LineType::Heading4 => HeadingLevel::Level4,
+            LineType::Heading5 => HeadingLevel::Level5,
+            LineType::Heading6 => HeadingLevel::Level6,
             _ => unreachable!(),
         };
 
         match self.tokenizer.consume(line_type)? {
             Err(err) => Some(Err(err)),
-            Ok(line) => Some(Ok(Block::Heading(level, line.value()?.trim().into()))),
-        }
-    }
-}
+            Ok(line) => {
+                let mut accumulator = TextAccumulator::new();
 
-impl<'a, S, I> Iterator for BlockParser<'a, S, I>
-where
-    S: IntoParserInput<'a>,
-    I: Iterator<Item = S>,
-{
-    type Item = Result<Block, ParseError>;
+                accumulator.add(&line.value()?, self.tokenizer.line());
 
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            match self.tokenizer.peek()? {
-                Err(..) => consume_error!(self.tokenizer),
-                Ok(LineType::Blank) => {
-                    self.tokenizer.consume_raw();
-                    continue;
-                }
-                Ok(line_type) => {
-                    return match line_type {
-                        LineType::Text => self.parse_text(),
-                        LineType::Quote => self.parse_quote(),
-                        LineType::Heading1 => self.parse_heading(LineType::Heading1),
-                        LineType::Heading2 => self.parse_heading(LineType::Heading2),
-                        LineType::Heading3 => self.parse_heading(LineType::Heading3),
-                        _ => unimplemented!(),
+                let content = accumulator.consume(&mut self.inline_parser);
+                let number = if annotations.numbered {
+                    self.number_heading(level)
+                } else {
+                    None
+                };
+
+                // Cross-references show the number of the heading, or its title if it has none
+                let label = annotations.label.and_then(|label| {
+                    let text = match number {
+                        Some(ref number) => ast::format_number(number),
+                        None => content.plain_text(),
                     };
-                }
-            };
+
+                    self.define_label(label, &text)
+                });
+
+                Some(Ok(Block::Heading {
+                    level,
+                    content,
+                    number,
+                    label,
+                }))
+            }
         }
     }
-}
 
-impl TextAccumulator {
-    pub fn new() -> Self {
-        TextAccumulator { buffer: String::new() }
+    ///
+    /// Resolves the cross-references to `label`. Returns `None` if the label is already used.
+    ///
+    fn define_label(&mut self, label: String, text: &str) -> Option<String> {
+        // Blocks without text (e.g. a heading containing only an image) are referred to by label
+        let text = if text.trim().is_empty() { &label } else { text };
+
+        if !self.inline_parser.define_label(&label, text) {
+            self.diagnose(DiagnosticKind::DuplicateLabel(label));
+
+            return None;
+        }
+
+        self.pending.define_label(&label, text);
+
+        Some(label)
     }
 
     ///
-    /// Adds a new line to the current accumulated text.
+    /// Returns the number of the next heading of `level`, if headings are numbered.
     ///
-    pub fn add(&mut self, line: &str) {
-        if !self.buffer.is_empty() {
-            self.buffer.push_str(" ");
+    fn number_heading(&mut self, level: HeadingLevel) -> Option<Vec<usize>> {
+        let numbers = self.heading_numbers.as_mut()?;
+        let depth = level as usize + 1;
+
+        // Skipped levels (e.g. a level 3 heading directly after a level 1 heading) are 1
+        if numbers.len() < depth {
+            numbers.resize(depth - 1, 1);
+            numbers.push(0);
+        } else {
+            numbers.truncate(depth);
         }
 
-        self.buffer.push_str(line.trim());
-    }
+        numbers[depth - 1] += 1;
 
-    pub fn consume(self) -> Text {
-        vec![Inline::Chunk(self.buffer)]
+        Some(numbers.clone())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    fn parse_list(
+        &mut self,
+        line_type: LineType,
+        style: NumberingStyle,
+    ) -> Option<Result<Block, ParseError>> {
+        let mut items = Vec::new();
 
-    macro_rules! unwrap {
-        ($value:expr) => {
-            $value.unwrap().unwrap()
+        loop {
+            match self.tokenizer.peek() {
+                Some(Err(..)) => consume_error!(self.tokenizer),
+                Some(Ok(ref peeked)) if *peeked == line_type => {
+                    let length = self.tokenizer.peek_raw().map_or(0, str::len);
+                    // unwrapping here is safe
+                    let line = self.tokenizer.consume(line_type).unwrap().unwrap();
+                    let number = match line {
+                        Line::OrderedList(number, _) => number,
+                        _ => None,
+                    };
+                    let value = line.value().unwrap();
+
+                    // The value is the end of the line, so the checkbox starts where it starts
+                    let item = self.parse_list_item(number, &value, length - value.len());
+
+                    items.push(item);
+                }
+                _ => break,
+            }
         }
-    }
 
-    #[test]
-    fn it_works() {
-        let mut parser = BlockParser::from_string("Lorem ipsum\ndolor sit amet");
+        let list_type = match line_type {
+            LineType::UnorderedList => ListType::Unordered,
+            LineType::OrderedList => {
+                ListType::Ordered {
+                    start: items.first().and_then(|item| item.number).unwrap_or(1),
+                    style,
+                }
+            }
+            _ => unreachable!(),
+        };
 
-        assert_eq!(
-            Block::Paragraph(vec![Inline::Chunk("Lorem ipsum dolor sit amet".into())]),
-            unwrap!(parser.next())
-        );
+        Some(Ok(Block::List(list_type, items)))
     }
 
-    #[test]
-    fn parsing_headings_works() {
-        let mut parser = BlockParser::from_string("# hello world\n##    level 2\n### three");
+    fn parse_definition_list(
+        &mut self,
+        mut term: TextAccumulator,
+    ) -> Option<Result<Block, ParseError>> {
+        let mut entries = Vec::new();
 
-        assert_eq!(
-            Block::Heading(HeadingLevel::Level1, "hello world".into()),
-            unwrap!(parser.next())
-        );
+        loop {
+            let term_text = term.consume(&mut self.inline_parser);
+            let mut definitions = Vec::new();
 
-        assert_eq!(
-            Block::Heading(HeadingLevel::Level2, "level 2".into()),
-            unwrap!(parser.next())
-        );
+            loop {
+                match self.tokenizer.peek() {
+                    Some(Err(..)) => consume_error!(self.tokenizer),
+                    Some(Ok(LineType::Definition)) => {
+                        // unwrapping here is safe
+                        let line = self.tokenizer.consume(LineType::Definition).unwrap().unwrap();
+                        let mut accumulator = TextAccumulator::new();
 
-        assert_eq!(
-            Block::Heading(HeadingLevel::Level3, "three".into()),
-            unwrap!(parser.next())
-        );
-    }
+                        accumulator.add(&line.value().unwrap(), self.tokenizer.line());
 
-    #[test]
-    fn parsing_quote_works() {
-        let mut parser = BlockParser::from_string("> Foo\n> bar baz");
+                        definitions.push(Block::Paragraph(
+                            accumulator.consume(&mut self.inline_parser),
+                        ));
+                    }
+                    _ => break,
+                }
+            }
 
-        assert_eq!(
-            Block::Quote(vec![Inline::Chunk("Foo bar baz".into())]),
-            unwrap!(parser.next())
-        );
-    }
+            entries.push((term_text, definitions));
 
-    #[test]
-    fn parsing_text_works() {
-        let mut parser = BlockParser::from_string("Foo\n    \tbar baz");
+            // Text lines directly following a definition start the next term
+            // if they are followed by a definition themselves
+            let mut lines = Vec::new();
 
-        assert_eq!(
-            Block::Paragraph(vec![Inline::Chunk("Foo bar baz".into())]),
-            unwrap!(parser.next())
-        );
+            loop {
+                match self.tokenizer.peek() {
+                    Some(Err(..)) => consume_error!(self.tokenizer),
+                    Some(Ok(LineType::Text)) => {
+                        // unwrapping here is safe
+                        let line = self.tokenizer.consume(LineType::Text).unwrap().unwrap();
+
+                        lines.push((line.value().unwrap().into_owned(), self.tokenizer.line()));
+                    }
+                    _ => break,
+                }
+            }
+
+            if lines.is_empty() {
+                break;
+            }
+
+            if let Some(Ok(LineType::Definition)) = self.tokenizer.peek() {
+                term = TextAccumulator::new();
+
+                for (value, position) in lines {
+                    if let Some(level) = block_tokenizer::excessive_heading_level(&value) {
+                        self.diagnose_at(position, DiagnosticKind::HeadingLevelTooDeep(level));
+                    }
+
+                    term.add(&value, position);
+                }
+            } else {
+                // Otherwise they are parsed again, as the block following the list
+                let content = lines.iter().map(|line| &*line.0).collect::<Vec<_>>().join("\n");
+
+                self.tokenizer.splice(lines[0].1, &content);
+
+                break;
+            }
+        }
+
+        Some(Ok(Block::DefinitionList(entries)))
     }
 
-    #[test]
-    fn blank_lines_are_ignored() {
-        let mut parser = BlockParser::from_string("   \n \t \nfoo");
+    fn parse_decorated(&mut self) -> Option<Result<Block, ParseError>> {
+        let mut annotations = Annotations::default();
+        // The line of the last decorator
+        let mut line;
 
-        assert_eq!(
-            Block::Paragraph(vec![Inline::Chunk("foo".into())]),
-            unwrap!(parser.next())
+        let decorator = loop {
+            let decorator = match self.tokenizer.consume(LineType::Decorator)? {
+                Err(err) => return Some(Err(err)),
+                Ok(line) => parse_decorator(&line.value()?),
+            };
+
+            line = self.tokenizer.line();
+
+            match decorator {
+                Some(Decorator::Unnumbered) => annotations.numbered = false,
+                Some(Decorator::Label(ref label)) if is_reserved_label(label) => {
+                    self.diagnose_at(line, DiagnosticKind::ReservedLabel(label.clone()));
+                }
+                Some(Decorator::Label(label)) => annotations.label = Some(label),
+                decorator => break decorator,
+            }
+
+            if let Some(Ok(LineType::Decorator)) = self.tokenizer.peek() {
+                continue;
+            }
+
+            break None;
+        };
+
+        // Includes don't need a following block
+        if let Some(Decorator::Include(path)) = decorator {
+            self.include(&path, line);
+            // The included blocks replace the decorator
+            self.block_start = None;
+
+            return self.parse_block();
+        }
+
+        // Figures need a following block, so they are reported even at the end of the input
+        if let Some(Decorator::Figure(caption)) = decorator {
+            return self.parse_figure(&caption, annotations, line);
+        }
+
+        // Apart from figures, only headings can carry a label
+        if let Some(label) = annotations.label.take() {
+            let heading = match self.tokenizer.peek() {
+                Some(Ok(line_type)) => decorator.is_none() && is_heading(line_type),
+                _ => false,
+            };
+
+            if heading {
+                annotations.label = Some(label);
+            } else {
+                self.diagnose_at(line, DiagnosticKind::UnusedLabel(label));
+            }
+        }
+
+        if let Some(Decorator::If(flag)) = decorator {
+            // Blank lines before the fence would otherwise let its content through
+            while let Some(Ok(LineType::Blank)) = self.tokenizer.peek() {
+                self.tokenizer.consume_raw();
+            }
+
+            return match self.tokenizer.peek() {
+                Some(Ok(LineType::Divider)) => self.parse_conditional(&flag),
+                _ => {
+                    self.drop_unfenced(line);
+                    self.parse_block()
+                }
+            };
+        }
+
+        match (decorator, self.tokenizer.peek()?) {
+            (_, Err(..)) => consume_error!(self.tokenizer),
+            (Some(Decorator::List(style)), Ok(LineType::OrderedList)) => {
+                self.parse_list(LineType::OrderedList, style)
+            }
+            (Some(Decorator::Math), Ok(LineType::Divider)) => {
+                self.parse_fenced().map(|result| result.map(Block::Math))
+            }
+            (Some(Decorator::Code(language)), Ok(LineType::Divider)) => {
+                self.parse_fenced().map(|result| {
+                    result.map(|code| Block::Preformatted(Some(Decorator::Code(language)), code))
+                })
+            }
+            (Some(Decorator::Else), Ok(LineType::Divider)) => {
+                self.diagnose_at(line, DiagnosticKind::UnexpectedElse);
+
+                match self.parse_fenced()? {
+                    Ok(..) => self.parse_block(),
+                    Err(err) => Some(Err(err)),
+                }
+            }
+            (Some(Decorator::Else), Ok(..)) => {
+                self.diagnose_at(line, DiagnosticKind::UnexpectedElse);
+                self.drop_unfenced(line);
+                self.parse_block()
+            }
+            (None, Ok(line_type)) if is_heading(line_type) => {
+                self.parse_heading(line_type, annotations)
+            }
+            // Decorators that don't apply to the following block are ignored
+            _ => self.parse_block(),
+        }
+    }
+
+    ///
+    /// Parses the content of the fenced block following `[if <flag>]` if the flag is set,
+    /// or else the content of a directly following `[else]` block.
+    ///
+    fn parse_conditional(&mut self, flag: &str) -> Option<Result<Block, ParseError>> {
+        let is_set = self.flags.contains(flag);
+        // The first line after the divider
+        let start = self.tokenizer.line() + 2;
+        let mut branch = match self.parse_fenced()? {
+            Ok(content) if is_set => Some((start, content)),
+            Ok(..) => None,
+            Err(err) => return Some(Err(err)),
+        };
+
+        let next = self.tokenizer.peek_raw().and_then(block_tokenizer::decorator_value);
+
+        if let Some(Decorator::Else) = next.and_then(parse_decorator) {
+            self.tokenizer.consume_raw();
+
+            if let Some(Ok(LineType::Divider)) = self.tokenizer.peek() {
+                let start = self.tokenizer.line() + 2;
+
+                match self.parse_fenced()? {
+                    Ok(content) if !is_set => branch = Some((start, content)),
+                    Ok(..) => (),
+                    Err(err) => return Some(Err(err)),
+                }
+            } else {
+                let line = self.tokenizer.line();
+
+                self.drop_unfenced(line);
+            }
+        }
+
+        if let Some((start, content)) = branch {
+            self.tokenizer.splice(start, &content);
+        }
+
+        // The content of the branch replaces the conditional block
+        self.block_start = None;
+
+        self.parse_block()
+    }
+
+    ///
+    /// Drops the lines up to the next blank line after an `[if]` or `[else]` decorator
+    /// at `line` that isn't followed by a fenced block, so that its content never shows up
+    /// in the wrong edition.
+    ///
+    fn drop_unfenced(&mut self, line: usize) {
+        self.diagnose_at(line, DiagnosticKind::UnfencedCondition);
+
+        while let Some(Ok(LineType::Blank)) = self.tokenizer.peek() {
+            self.tokenizer.consume_raw();
+        }
+
+        loop {
+            match self.tokenizer.peek() {
+                Some(Ok(LineType::Blank)) | None => break,
+                _ => {
+                    self.tokenizer.consume_raw();
+                }
+            }
+        }
+    }
+
+    ///
+    /// Defines the variables of the front matter, a fenced block at the start of the document.
+    ///
+    fn parse_front_matter(&mut self) -> Option<ParseError> {
+        // The first line after the divider
+        let start = self.tokenizer.line() + 2;
+        let front_matter = match self.parse_fenced()? {
+            Ok(front_matter) => front_matter,
+            Err(err) => return Some(err),
+        };
+
+        for (index, line) in front_matter.split('\n').enumerate() {
+            if block_tokenizer::is_blank(line) {
+                continue;
+            }
+
+            let definition = line
+                .split_once(constants::VARIABLE_DEFINITION_TOKEN)
+                .map(|(name, value)| (name.trim(), value.trim()))
+                .filter(|&(name, _)| inline_parser::is_variable_name(name));
+
+            match definition {
+                // Variables passed to the parser take precedence
+                Some((name, _)) if self.variables.contains_key(name) => (),
+                Some((name, value)) => self.inline_parser.define_variable(name, value),
+                None => self.diagnose_at(start + index, DiagnosticKind::InvalidVariableDefinition),
+            }
+        }
+
+        None
+    }
+
+    ///
+    /// Continues parsing with the blocks of the file `path`, which is included at `line`.
+    ///
+    fn include(&mut self, path: &str, line: usize) {
+        if self.name.is_none() {
+            self.name = self.resolve_path();
+        }
+
+        let result = match self.resolver {
+            Some(ref resolver) => {
+                let parent = self.tokenizer.includes().last().or(self.name.as_deref());
+
+                resolver.resolve(path, parent).map_err(|err| err.to_string())
+            }
+            None => Err("includes are not enabled".into()),
+        };
+
+        let (name, content) = match result {
+            Ok(file) => file,
+            Err(reason) => {
+                return self.diagnose_at(line, DiagnosticKind::IncludeFailed(path.into(), reason));
+            }
+        };
+
+        let is_cycle = self.name.as_deref() == Some(&name) ||
+            self.tokenizer.includes().any(|include| include == name);
+
+        if is_cycle {
+            self.diagnose_at(line, DiagnosticKind::IncludeCycle(path.into()));
+        } else if self.tokenizer.includes().count() >= constants::MAX_INCLUDE_DEPTH {
+            self.diagnose_at(line, DiagnosticKind::IncludeTooDeep(path.into()));
+        } else {
+            self.tokenizer.include(name, &content);
+        }
+    }
+
+    ///
+    /// Returns the name of the document, resolving its path like an include.
+    /// Falls back to the path if it can't be resolved.
+    ///
+    fn resolve_path(&self) -> Option<String> {
+        let path = self.path.as_ref()?;
+
+        match self.resolver {
+            Some(ref resolver) => match resolver.resolve(path, None) {
+                Ok((name, _)) => Some(name),
+                Err(..) => Some(path.clone()),
+            },
+            None => None,
+        }
+    }
+
+    ///
+    /// Parses the block following a `[figure]` decorator, which may be decorated itself.
+    ///
+    fn parse_figure(
+        &mut self,
+        caption: &str,
+        annotations: Annotations,
+        line: usize,
+    ) -> Option<Result<Block, ParseError>> {
+        match self.tokenizer.peek() {
+            Some(Err(..)) => consume_error!(self.tokenizer),
+            Some(Ok(line_type)) if is_figure_body(line_type) => (),
+            _ => {
+                self.diagnose_at(line, DiagnosticKind::FigureWithoutBody);
+
+                return self.parse_block();
+            }
+        }
+
+        let mut accumulator = TextAccumulator::new();
+
+        accumulator.add(caption, line);
+
+        // The caption is parsed first, so that its unresolved references come first
+        let caption = accumulator.consume(&mut self.inline_parser);
+
+        let body = match self.parse_block() {
+            Some(Ok(Block::FootnoteDefinition(footnote))) => {
+                // Footnotes are rendered at the end of the document, never in a figure
+                self.diagnose_at(line, DiagnosticKind::FigureWithoutBody);
+
+                return Some(Ok(Block::FootnoteDefinition(footnote)));
+            }
+            Some(Ok(body)) => body,
+            Some(Err(err)) => return Some(Err(err)),
+            None => {
+                self.diagnose_at(line, DiagnosticKind::FigureWithoutBody);
+
+                return None;
+            }
+        };
+
+        let number = if annotations.numbered {
+            let count = self.figure_numbers.entry(FigureKind::of(&body)).or_insert(0);
+
+            *count += 1;
+
+            Some(*count)
+        } else {
+            None
+        };
+
+        let label = annotations.label.and_then(|label| {
+            let text = match number {
+                Some(number) => number.to_string(),
+                None => caption.plain_text(),
+            };
+
+            self.define_label(label, &text)
+        });
+
+        Some(Ok(Block::Figure {
+            caption,
+            body: Box::new(body),
+            number,
+            label,
+        }))
+    }
+
+    ///
+    /// Parses the raw content between a divider and the next divider identical to it.
+    ///
+    fn parse_fenced(&mut self) -> Option<Result<String, ParseError>> {
+        let fence = match self.tokenizer.consume_raw()? {
+            Ok(line) => line.trim_end().to_string(),
+            Err(err) => return Some(Err(err)),
+        };
+
+        let start = self.tokenizer.line();
+        // Fenced blocks end with the included file (or branch) they start in
+        let depth = self.tokenizer.spliced_depth();
+        let mut lines = Vec::new();
+
+        loop {
+            if self.tokenizer.spliced_depth() < depth {
+                // The blank line separating the spliced lines from the rest is not content
+                lines.pop();
+                self.diagnose_at(start, DiagnosticKind::UnclosedFence);
+
+                break;
+            }
+
+            match self.tokenizer.consume_raw() {
+                Some(Ok(ref line)) if line.trim_end() == fence => break,
+                Some(Ok(line)) => lines.push(line.into_owned()),
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    self.diagnose_at(start, DiagnosticKind::UnclosedFence);
+
+                    break;
+                }
+            }
+        }
+
+        Some(Ok(lines.join("\n")))
+    }
+
+    fn parse_list_item(&mut self, number: Option<u32>, value: &str, column: usize) -> ListItem {
+        let (checked, content) = block_tokenizer::split_checkbox(value);
+        let mut accumulator = TextAccumulator::new();
+
+        accumulator.add(content, self.tokenizer.line());
+
+        let checkbox_span = checked.map(|_| {
+            let (file, line) = self.tokenizer.locate(self.tokenizer.line());
+
+            Span {
+                file: file.map(String::from),
+                line,
+                range: column..column + constants::CHECKBOX_CHECKED_TOKEN.len(),
+            }
+        });
+
+        ListItem {
+            checked,
+            checkbox_span,
+            number,
+            content: accumulator.consume(&mut self.inline_parser),
+        }
+    }
+
+    fn parse_reference_definition(&mut self) -> Option<ParseError> {
+        let (label, target) = match self.tokenizer.consume(LineType::ReferenceDefinition) {
+            Some(Ok(Line::ReferenceDefinition(label, target))) => (label, target),
+            Some(Err(err)) => return Some(err),
+            _ => unreachable!(),
+        };
+
+        if self.inline_parser.define(&label, &target) {
+            self.pending.define(&label, &target);
+        } else {
+            self.diagnose(DiagnosticKind::DuplicateReferenceDefinition(label.into()));
+        }
+
+        None
+    }
+
+    fn parse_footnote_definition(&mut self) -> Option<Result<Block, ParseError>> {
+        let (label, content) = match self.tokenizer.consume(LineType::FootnoteDefinition)? {
+            Ok(Line::FootnoteDefinition(label, content)) => (label, content),
+            Err(err) => return Some(Err(err)),
+            _ => unreachable!(),
+        };
+
+        let line = self.tokenizer.line();
+        let mut accumulator = TextAccumulator::new();
+
+        accumulator.add(&content, line);
+
+        // Following text lines continue the footnote
+        if let Err(err) = self.accumulate_text(&mut accumulator) {
+            return Some(Err(err));
+        }
+
+        match self.inline_parser.define_footnote(&label, line) {
+            Some(number) => {
+                let content = accumulator.consume(&mut self.inline_parser);
+                let referenced = self.inline_parser.is_footnote_referenced(&label);
+
+                Some(Ok(Block::FootnoteDefinition(Footnote {
+                    number,
+                    label: label.into(),
+                    content,
+                    referenced,
+                })))
+            }
+            None => {
+                self.diagnose_at(line, DiagnosticKind::DuplicateFootnoteDefinition(label.into()));
+
+                self.parse_block()
+            }
+        }
+    }
+
+    fn parse_block(&mut self) -> Option<Result<Block, ParseError>> {
+        loop {
+            match self.tokenizer.peek()? {
+                Err(..) => consume_error!(self.tokenizer),
+                Ok(LineType::Blank) => {
+                    self.tokenizer.consume_raw();
+                    continue;
+                }
+                // A divider before anything else starts the front matter
+                Ok(LineType::Divider) if !self.seen_block => {
+                    self.seen_block = true;
+
+                    if let Some(err) = self.parse_front_matter() {
+                        return Some(Err(err));
+                    }
+
+                    continue;
+                }
+                Ok(LineType::ReferenceDefinition) => {
+                    self.seen_block = true;
+
+                    if let Some(err) = self.parse_reference_definition() {
+                        return Some(Err(err));
+                    }
+
+                    continue;
+                }
+                // Other dividers only fence the content of decorated blocks
+                Ok(LineType::Divider) => {
+                    self.tokenizer.consume_raw();
+                    self.diagnose(DiagnosticKind::UnexpectedDivider);
+
+                    continue;
+                }
+                Ok(line_type) => {
+                    self.seen_block = true;
+
+                    if self.block_start.is_none() {
+                        self.block_start = Some(self.tokenizer.line() + 1);
+                    }
+
+                    return match line_type {
+                        LineType::Text => self.parse_text(),
+                        LineType::Quote => self.parse_quote(),
+                        line_type if is_heading(line_type) => {
+                            self.parse_heading(line_type, Annotations::default())
+                        }
+                        LineType::UnorderedList => {
+                            self.parse_list(LineType::UnorderedList, NumberingStyle::Decimal)
+                        }
+                        LineType::OrderedList => {
+                            self.parse_list(LineType::OrderedList, NumberingStyle::Decimal)
+                        }
+                        LineType::Decorator => self.parse_decorated(),
+                        LineType::FootnoteDefinition => self.parse_footnote_definition(),
+                        // A definition without a term
+                        LineType::Definition => {
+                            self.parse_definition_list(TextAccumulator::new())
+                        }
+                        // Blank lines, dividers and reference definitions are handled above
+                        _ => unreachable!(),
+                    };
+                }
+            };
+        }
+    }
+
+    fn push_comments(&mut self, comments: Vec<(usize, String)>) {
+        for (_, comment) in comments {
+            self.pending.push(Ok(Block::Comment(comment)), Vec::new());
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        self.finished = true;
+
+        if let Some(position) = self.tokenizer.take_unclosed_comment() {
+            self.diagnose_at(position, DiagnosticKind::UnclosedComment);
+        }
+
+        for reference in self.pending.finish() {
+            let kind = if reference.is_cross_reference {
+                DiagnosticKind::UndefinedLabel(reference.label)
+            } else {
+                DiagnosticKind::UndefinedReference(reference.label)
+            };
+
+            self.diagnose_at(reference.line, kind);
+        }
+
+        for (label, line) in self.inline_parser.undefined_footnotes() {
+            self.diagnose_at(line, DiagnosticKind::UndefinedFootnote(label));
+        }
+
+        for (label, line) in self.inline_parser.unreferenced_footnotes() {
+            self.diagnose_at(line, DiagnosticKind::UnreferencedFootnote(label));
+        }
+    }
+}
+
+fn parse_decorator(value: &str) -> Option<Decorator> {
+    let mut parts = value.split_whitespace();
+
+    match parts.next()? {
+        "code" => Some(Decorator::Code(parts.next().map(Into::into))),
+        "list" => parse_numbering_style(parts.next()?).map(Decorator::List),
+        "math" => Some(Decorator::Math),
+        "unnumbered" => Some(Decorator::Unnumbered),
+        "if" => parts.next().map(|flag| Decorator::If(flag.into())),
+        "else" => Some(Decorator::Else),
+        "include" => {
+            let path = value.trim_start()["include".len()..].trim();
+
+            if path.is_empty() {
+                None
+            } else {
+                Some(Decorator::Include(path.into()))
+            }
+        }
+        "figure" => {
+            let caption = value.trim_start()["figure".len()..].trim();
+
+            Some(Decorator::Figure(caption.into()))
+        }
+        "label" => {
+            parts
+                .next()
+                .filter(|label| inline_parser::is_cross_reference_label(label))
+                .map(|label| Decorator::Label(label.into()))
+        }
+        _ => None,
+    }
+}
+
+///
+/// Checks if `label` starts like an id generated for sections or footnotes.
+///
+fn is_reserved_label(label: &str) -> bool {
+    constants::RESERVED_LABEL_PREFIXES
+        .iter()
+        .any(|prefix| label.starts_with(prefix))
+}
+
+///
+/// Whether a line of `line_type` can start the body of a figure. Definitions of references
+/// and footnotes can't, since they are not shown where they are written.
+///
+fn is_figure_body(line_type: LineType) -> bool {
+    !matches!(
+        line_type,
+        LineType::Blank |
+            LineType::Divider |
+            LineType::ReferenceDefinition |
+            LineType::FootnoteDefinition
+    )
+}
+
+fn is_heading(line_type: LineType) -> bool {
+    matches!(
+        line_type,
+        LineType::Heading1 |
+            LineType::Heading2 |
+            LineType::Heading3 |
+            LineType::Heading4 |
+            LineType::Heading5 |
+            LineType::Heading6
+    )
+}
+
+fn parse_numbering_style(value: &str) -> Option<NumberingStyle> {
+    match value {
+        "decimal" => Some(NumberingStyle::Decimal),
+        "lower-alpha" => Some(NumberingStyle::LowerAlpha),
+        "upper-alpha" => Some(NumberingStyle::UpperAlpha),
+        "lower-roman" => Some(NumberingStyle::LowerRoman),
+        "upper-roman" => Some(NumberingStyle::UpperRoman),
+        _ => None,
+    }
+}
+
+impl<'a, S, I> Iterator for BlockParser<'a, S, I>
+where
+    S: IntoParserInput<'a>,
+    I: Iterator<Item = S>,
+{
+    type Item = Result<Block, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(result) = self.pending.pop_resolved() {
+                return Some(result);
+            }
+
+            self.block_start = None;
+
+            match self.parse_block() {
+                Some(result) => {
+                    for (name, line) in self.inline_parser.take_undefined_variables() {
+                        self.diagnose_at(line, DiagnosticKind::UndefinedVariable(name));
+                    }
+
+                    let unresolved = self.inline_parser.take_unresolved();
+                    let comments = self.tokenizer.take_comments();
+                    let inline_parser = &self.inline_parser;
+
+                    self.pending
+                        .reference_footnotes(|label| inline_parser.is_footnote_referenced(label));
+
+                    let unreferenced = match result {
+                        Ok(Block::FootnoteDefinition(ref footnote)) => !footnote.referenced,
+                        _ => false,
+                    };
+
+                    if unresolved.is_empty() &&
+                        !unreferenced &&
+                        self.pending.is_empty() &&
+                        comments.is_empty()
+                    {
+                        return Some(result);
+                    }
+
+                    let start = self.block_start.unwrap_or(0);
+                    let (before, after): (Vec<_>, Vec<_>) =
+                        comments.into_iter().partition(|&(position, _)| position < start);
+
+                    self.push_comments(before);
+                    self.pending.push(result, unresolved);
+                    self.push_comments(after);
+                }
+                None => {
+                    let comments = self.tokenizer.take_comments();
+
+                    self.push_comments(comments);
+                    self.finish();
+
+                    return self.pending.pop_resolved();
+                }
+            }
+        }
+    }
+}
+
+impl TextAccumulator {
+    pub fn new() -> Self {
+        TextAccumulator {
+            buffer: String::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    ///
+    /// Adds a new line to the current accumulated text.
+    /// `number` is the number of the line in the input, used for diagnostics.
+    ///
+    pub fn add(&mut self, line: &str, number: usize) {
+        if !self.buffer.is_empty() {
+            self.buffer.push_str(" ");
+        }
+
+        self.lines.push((self.buffer.len(), number));
+        self.buffer.push_str(line.trim());
+    }
+
+    pub fn consume(self, parser: &mut InlineParser) -> Text {
+        parser.parse(&self.buffer, &self.lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::Inline;
+
+    macro_rules! unwrap {
+        ($value:expr) => {
+            $value.unwrap().unwrap()
+        }
+    }
+
+    #[test]
+    fn it_works() {
+        let mut parser = BlockParser::from_string("Lorem ipsum\ndolor sit amet");
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("Lorem ipsum dolor sit amet".into())]),
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn parsing_headings_works() {
+        let mut parser = BlockParser::from_string("# hello world\n##    level 2\n### three");
+
+        assert_eq!(
+            Block::Heading {
+                level: HeadingLevel::Level1,
+                content: vec![Inline::Chunk("hello world".into())],
+                number: None,
+                label: None,
+            },
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Heading {
+                level: HeadingLevel::Level2,
+                content: vec![Inline::Chunk("level 2".into())],
+                number: None,
+                label: None,
+            },
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Heading {
+                level: HeadingLevel::Level3,
+                content: vec![Inline::Chunk("three".into())],
+                number: None,
+                label: None,
+            },
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn parsing_deep_headings_works() {
+        let mut parser = BlockParser::from_string("#### four\n##### five\n###### six\n####### seven");
+
+        assert_eq!(
+            Block::Heading {
+                level: HeadingLevel::Level4,
+                content: vec![Inline::Chunk("four".into())],
+                number: None,
+                label: None,
+            },
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Heading {
+                level: HeadingLevel::Level5,
+                content: vec![Inline::Chunk("five".into())],
+                number: None,
+                label: None,
+            },
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Heading {
+                level: HeadingLevel::Level6,
+                content: vec![Inline::Chunk("six".into())],
+                number: None,
+                label: None,
+            },
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("####### seven".into())]),
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            &[Diagnostic::new(4, DiagnosticKind::HeadingLevelTooDeep(7))],
+            parser.diagnostics()
+        );
+    }
+
+    #[test]
+    fn parsing_quote_works() {
+        let mut parser = BlockParser::from_string("> Foo\n> bar baz");
+
+        assert_eq!(
+            Block::Quote(vec![Inline::Chunk("Foo bar baz".into())]),
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn parsing_text_works() {
+        let mut parser = BlockParser::from_string("Foo\n    \tbar baz");
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("Foo bar baz".into())]),
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn parsing_lists_works() {
+        let mut parser = BlockParser::from_string("- foo\n- bar\n. baz");
+
+        assert_eq!(
+            Block::List(
+                ListType::Unordered,
+                vec![
+                    ListItem {
+                        checked: None,
+                        checkbox_span: None,
+                        number: None,
+                        content: vec![Inline::Chunk("foo".into())],
+                    },
+                    ListItem {
+                        checked: None,
+                        checkbox_span: None,
+                        number: None,
+                        content: vec![Inline::Chunk("bar".into())],
+                    },
+                ],
+            ),
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::List(
+                ListType::Ordered {
+                    start: 1,
+                    style: NumberingStyle::Decimal,
+                },
+                vec![
+                    ListItem {
+                        checked: None,
+                        checkbox_span: None,
+                        number: None,
+                        content: vec![Inline::Chunk("baz".into())],
+                    },
+                ],
+            ),
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn parsing_checkboxes_works() {
+        let mut parser = BlockParser::from_string("- [ ] do\n- [x] this\n- [x]and that");
+
+        assert_eq!(
+            Block::List(
+                ListType::Unordered,
+                vec![
+                    ListItem {
+                        checked: Some(false),
+                        checkbox_span: Some(Span {
+                            file: None,
+                            line: 1,
+                            range: 2..5,
+                        }),
+                        number: None,
+                        content: vec![Inline::Chunk("do".into())],
+                    },
+                    ListItem {
+                        checked: Some(true),
+                        checkbox_span: Some(Span {
+                            file: None,
+                            line: 2,
+                            range: 2..5,
+                        }),
+                        number: None,
+                        content: vec![Inline::Chunk("this".into())],
+                    },
+                    ListItem {
+                        checked: None,
+                        checkbox_span: None,
+                        number: None,
+                        content: vec![Inline::Chunk("[x]and that".into())],
+                    },
+                ],
+            ),
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn parsing_numbered_lists_works() {
+        let mut parser = BlockParser::from_string("[list upper-roman]\n3. foo\n. bar\n7. baz\n\n[foo]\n. qux");
+
+        assert_eq!(
+            Block::List(
+                ListType::Ordered {
+                    start: 3,
+                    style: NumberingStyle::UpperRoman,
+                },
+                vec![
+                    ListItem {
+                        checked: None,
+                        checkbox_span: None,
+                        number: Some(3),
+                        content: vec![Inline::Chunk("foo".into())],
+                    },
+                    ListItem {
+                        checked: None,
+                        checkbox_span: None,
+                        number: None,
+                        content: vec![Inline::Chunk("bar".into())],
+                    },
+                    ListItem {
+                        checked: None,
+                        checkbox_span: None,
+                        number: Some(7),
+                        content: vec![Inline::Chunk("baz".into())],
+                    },
+                ],
+            ),
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::List(
+                ListType::Ordered {
+                    start: 1,
+                    style: NumberingStyle::Decimal,
+                },
+                vec![
+                    ListItem {
+                        checked: None,
+                        checkbox_span: None,
+                        number: None,
+                        content: vec![Inline::Chunk("qux".into())],
+                    },
+                ],
+            ),
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn blank_lines_are_ignored() {
+        let mut parser = BlockParser::from_string("   \n \t \nfoo");
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("foo".into())]),
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn unknown_decorators_are_ignored() {
+        let mut parser = BlockParser::from_string("[table]\nfoo");
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("foo".into())]),
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn parsing_references_works() {
+        let mut parser = BlockParser::from_string(
+            "[Squid][squid] and ![Ink][ink]\n\n# [Home](/)\n\n[squid]: https://squid.rs\n\
+             [squid]: https://example.com\n\n- [Squid][squid]",
+        );
+
+        assert_eq!(
+            Block::Paragraph(vec![
+                Inline::Link {
+                    target: Some("https://squid.rs".into()),
+                    content: vec![Inline::Chunk("Squid".into())],
+                },
+                Inline::Chunk(" and ".into()),
+                Inline::Chunk("![Ink][ink]".into()),
+            ]),
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Heading {
+                level: HeadingLevel::Level1,
+                content: vec![
+                    Inline::Link {
+                        target: Some("/".into()),
+                        content: vec![Inline::Chunk("Home".into())],
+                    },
+                ],
+                number: None,
+                label: None,
+            },
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::List(
+                ListType::Unordered,
+                vec![
+                    ListItem {
+                        checked: None,
+                        checkbox_span: None,
+                        number: None,
+                        content: vec![
+                            Inline::Link {
+                                target: Some("https://squid.rs".into()),
+                                content: vec![Inline::Chunk("Squid".into())],
+                            },
+                        ],
+                    },
+                ],
+            ),
+            unwrap!(parser.next())
+        );
+
+        assert!(parser.next().is_none());
+
+        assert_eq!(
+            &[
+                Diagnostic::new(6, DiagnosticKind::DuplicateReferenceDefinition("squid".into())),
+                Diagnostic::new(1, DiagnosticKind::UndefinedReference("ink".into())),
+            ],
+            parser.diagnostics()
+        );
+    }
+
+    #[test]
+    fn footnotes_referenced_after_their_definition_are_referenced() {
+        let mut parser = BlockParser::from_string("[^ink]: sepia\n\nSquid[^ink]");
+
+        assert_eq!(
+            Block::FootnoteDefinition(Footnote {
+                number: 1,
+                label: "ink".into(),
+                content: vec![Inline::Chunk("sepia".into())],
+                referenced: true,
+            }),
+            unwrap!(parser.next())
+        );
+        assert_eq!(
+            Block::Paragraph(vec![
+                Inline::Chunk("Squid".into()),
+                Inline::FootnoteRef {
+                    label: "ink".into(),
+                    number: 1,
+                    occurrence: 1,
+                },
+            ]),
+            unwrap!(parser.next())
+        );
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn parsing_footnotes_works() {
+        let input = "Squid[^ink].\n\n[^ink]: Made of\n[sepia](/sepia).\n[^ink]: again\n\n\
+                     Octopus[^arms]\n\n[^tentacles]: eight";
+        let mut parser = BlockParser::from_string(input);
+        let reference = |label: &str, number| {
+            Inline::FootnoteRef {
+                label: label.into(),
+                number,
+                occurrence: 1,
+            }
+        };
+
+        assert_eq!(
+            Block::Paragraph(vec![
+                Inline::Chunk("Squid".into()),
+                reference("ink", 1),
+                Inline::Chunk(".".into()),
+            ]),
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::FootnoteDefinition(Footnote {
+                number: 1,
+                label: "ink".into(),
+                content: vec![
+                    Inline::Chunk("Made of ".into()),
+                    Inline::Link {
+                        target: Some("/sepia".into()),
+                        content: vec![Inline::Chunk("sepia".into())],
+                    },
+                    Inline::Chunk(".".into()),
+                ],
+                referenced: true,
+            }),
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("Octopus".into()), reference("arms", 2)]),
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::FootnoteDefinition(Footnote {
+                number: 3,
+                label: "tentacles".into(),
+                content: vec![Inline::Chunk("eight".into())],
+                referenced: false,
+            }),
+            unwrap!(parser.next())
+        );
+
+        assert!(parser.next().is_none());
+
+        assert_eq!(
+            &[
+                Diagnostic::new(5, DiagnosticKind::DuplicateFootnoteDefinition("ink".into())),
+                Diagnostic::new(7, DiagnosticKind::UndefinedFootnote("arms".into())),
+                Diagnostic::new(9, DiagnosticKind::UnreferencedFootnote("tentacles".into())),
+            ],
+            parser.diagnostics()
+        );
+    }
+
+    #[test]
+    fn parsing_definition_lists_works() {
+        let input = "Squid\n: a cephalopod\n: a parser\nInk\nsepia\n: dark\nNo definition\n\n\
+                     : lonely";
+        let mut parser = BlockParser::from_string(input);
+        let text = |value: &str| vec![Inline::Chunk(value.into())];
+        let paragraph = |value: &str| Block::Paragraph(vec![Inline::Chunk(value.into())]);
+
+        assert_eq!(
+            Block::DefinitionList(vec![
+                (text("Squid"), vec![paragraph("a cephalopod"), paragraph("a parser")]),
+                (text("Ink sepia"), vec![paragraph("dark")]),
+            ]),
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(paragraph("No definition"), unwrap!(parser.next()));
+
+        assert_eq!(
+            Block::DefinitionList(vec![(vec![], vec![paragraph("lonely")])]),
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn parsing_math_works() {
+        let input = "[math]\n---\nx^2\n\n----\n---\n\n[math]\n--\n\n[math]\n---\ny";
+        let mut parser = BlockParser::from_string(input);
+
+        assert_eq!(Block::Math("x^2\n\n----".into()), unwrap!(parser.next()));
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("--".into())]),
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(Block::Math("y".into()), unwrap!(parser.next()));
+        assert!(parser.next().is_none());
+
+        assert_eq!(
+            &[Diagnostic::new(12, DiagnosticKind::UnclosedFence)],
+            parser.diagnostics()
+        );
+    }
+
+    #[test]
+    fn numbering_headings_works() {
+        let input = "### Intro\n\n# One\n\n### One.One.One\n\n[unnumbered]\n## Notes\n\n\
+                     ## One.Two\n\n# Two";
+        let numbers = BlockParser::from_string(input)
+            .number_headings(true)
+            .map(|block| match block.unwrap() {
+                Block::Heading { number, .. } => number,
+                block => panic!("unexpected block {:?}", block),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                Some(vec![1, 1, 1]),
+                Some(vec![2]),
+                Some(vec![2, 1, 1]),
+                None,
+                Some(vec![2, 2]),
+                Some(vec![3]),
+            ],
+            numbers
+        );
+    }
+
+    #[test]
+    fn cross_references_work() {
+        let input = "See [@ink] and [@squid].\n\n[label squid]\n# Squid\n\n\
+                     [unnumbered]\n[label ink]\n## Ink\n\n[label squid]\n# Octopus\n\n[@arms]";
+        let mut parser = BlockParser::from_string(input).number_headings(true);
+
+        assert_eq!(
+            Block::Paragraph(vec![
+                Inline::Chunk("See ".into()),
+                Inline::CrossRef {
+                    label: "ink".into(),
+                    text: Some("Ink".into()),
+                },
+                Inline::Chunk(" and ".into()),
+                Inline::CrossRef {
+                    label: "squid".into(),
+                    text: Some("1".into()),
+                },
+                Inline::Chunk(".".into()),
+            ]),
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Heading {
+                level: HeadingLevel::Level1,
+                content: vec![Inline::Chunk("Squid".into())],
+                number: Some(vec![1]),
+                label: Some("squid".into()),
+            },
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Heading {
+                level: HeadingLevel::Level2,
+                content: vec![Inline::Chunk("Ink".into())],
+                number: None,
+                label: Some("ink".into()),
+            },
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Heading {
+                level: HeadingLevel::Level1,
+                content: vec![Inline::Chunk("Octopus".into())],
+                number: Some(vec![2]),
+                label: None,
+            },
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("[@arms]".into())]),
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            &[
+                Diagnostic::new(11, DiagnosticKind::DuplicateLabel("squid".into())),
+                Diagnostic::new(13, DiagnosticKind::UndefinedLabel("arms".into())),
+            ],
+            parser.diagnostics()
+        );
+    }
+
+    #[test]
+    fn dropped_labels_are_reported() {
+        let input = "[label c]\n[code]\n---\nx\n---\n\n[label fn-1]\n# Ink\n\n[@c]";
+        let mut parser = BlockParser::from_string(input);
+
+        assert_eq!(
+            Block::Preformatted(Some(Decorator::Code(None)), "x".into()),
+            unwrap!(parser.next())
+        );
+        assert_eq!(
+            Block::Heading {
+                level: HeadingLevel::Level1,
+                content: vec![Inline::Chunk("Ink".into())],
+                number: None,
+                label: None,
+            },
+            unwrap!(parser.next())
+        );
+        assert!(parser.by_ref().all(|block| block.is_ok()));
+
+        assert_eq!(
+            &[
+                Diagnostic::new(2, DiagnosticKind::UnusedLabel("c".into())),
+                Diagnostic::new(7, DiagnosticKind::ReservedLabel("fn-1".into())),
+                Diagnostic::new(10, DiagnosticKind::UndefinedLabel("c".into())),
+            ],
+            parser.diagnostics()
+        );
+    }
+
+    #[test]
+    fn parsing_figures_works() {
+        let input = "[label squid]\n[figure A *squid*]\n![squid](squid.png)\n\n\
+                     [figure Hello]\n[code]\n---\nsquid\n---\n\n\
+                     [figure Ink]\n![ink](ink.png)\n\nSee [@squid].";
+        let mut parser = BlockParser::from_string(input);
+
+        assert_eq!(
+            Block::Figure {
+                caption: vec![Inline::Chunk("A *squid*".into())],
+                body: Box::new(Block::Paragraph(vec![
+                    Inline::Image {
+                        source: Some("squid.png".into()),
+                        alt: "squid".into(),
+                    },
+                ])),
+                number: Some(1),
+                label: Some("squid".into()),
+            },
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Figure {
+                caption: vec![Inline::Chunk("Hello".into())],
+                body: Box::new(Block::Preformatted(Some(Decorator::Code(None)), "squid".into())),
+                number: Some(1),
+                label: None,
+            },
+            unwrap!(parser.next())
+        );
+
+        match unwrap!(parser.next()) {
+            Block::Figure { number, .. } => assert_eq!(Some(2), number),
+            block => panic!("unexpected block {:?}", block),
+        }
+
+        assert_eq!(
+            Block::Paragraph(vec![
+                Inline::Chunk("See ".into()),
+                Inline::CrossRef {
+                    label: "squid".into(),
+                    text: Some("1".into()),
+                },
+                Inline::Chunk(".".into()),
+            ]),
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn figures_without_body_are_reported() {
+        let paragraph = |value: &str| Block::Paragraph(vec![Inline::Chunk(value.into())]);
+        let inputs = vec![
+            "[figure Note]\n[^1]: note\n\nSee[^1]",
+            "[figure Link]\n[x]: http://x\n\nUnrelated",
+            "Unrelated\n\n[figure End]",
+        ];
+
+        for input in inputs {
+            let mut parser = BlockParser::from_string(input);
+            let blocks = parser.by_ref().map(Result::unwrap).collect::<Vec<_>>();
+
+            assert!(blocks.iter().all(|block| match *block {
+                Block::Figure { .. } => false,
+                _ => true,
+            }));
+            assert_eq!(
+                Some(&DiagnosticKind::FigureWithoutBody),
+                parser.diagnostics().first().map(Diagnostic::kind)
+            );
+        }
+
+        let mut parser = BlockParser::from_string("[figure Link]\n[x]: http://x\n\nUnrelated");
+
+        assert_eq!(paragraph("Unrelated"), unwrap!(parser.next()));
+    }
+
+    #[test]
+    fn parsing_code_works() {
+        let input = "[code rust]\n------\nfn main() {\n\n    # not a heading\n}\n------";
+        let mut parser = BlockParser::from_string(input);
+
+        assert_eq!(
+            Block::Preformatted(
+                Some(Decorator::Code(Some("rust".into()))),
+                "fn main() {\n\n    # not a heading\n}".into(),
+            ),
+            unwrap!(parser.next())
+        );
+
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn autolinking_works() {
+        let input = "Visit https://squid.rs.";
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("Visit https://squid.rs.".into())]),
+            unwrap!(BlockParser::from_string(input).next())
+        );
+
+        assert_eq!(
+            Block::Paragraph(vec![
+                Inline::Chunk("Visit ".into()),
+                Inline::Link {
+                    target: Some("https://squid.rs".into()),
+                    content: vec![Inline::Chunk("https://squid.rs".into())],
+                },
+                Inline::Chunk(".".into()),
+            ]),
+            unwrap!(BlockParser::from_string(input).autolink(true).next())
+        );
+    }
+
+    #[test]
+    fn escaped_line_starters_are_text() {
+        let mut parser =
+            BlockParser::from_string("\\# not a heading\n\\- nor a list\n\n- \\[x] item");
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("# not a heading - nor a list".into())]),
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::List(
+                ListType::Unordered,
+                vec![
+                    ListItem {
+                        checked: None,
+                        checkbox_span: None,
+                        number: None,
+                        content: vec![Inline::Chunk("[x] item".into())],
+                    },
+                ],
+            ),
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn cross_references_to_blocks_without_text_work() {
+        let input = "See [@x] and [a][b].\n\n[unnumbered]\n[label x]\n# ![](a.png)\n\n[b]: /url";
+        let mut parser = BlockParser::from_string(input);
+
+        assert_eq!(
+            Block::Paragraph(vec![
+                Inline::Chunk("See ".into()),
+                Inline::CrossRef {
+                    label: "x".into(),
+                    text: Some("x".into()),
+                },
+                Inline::Chunk(" and ".into()),
+                Inline::Link {
+                    target: Some("/url".into()),
+                    content: vec![Inline::Chunk("a".into())],
+                },
+                Inline::Chunk(".".into()),
+            ]),
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn includes_work() {
+        use resolver::MemoryResolver;
+
+        let resolver = MemoryResolver::new()
+            .file("arms.sq", "## Arms\n\n[include parts/ink.sq]\n\nEight arms.")
+            .file("parts/ink.sq", "Ink [arms][missing]\n\n[include ../arms.sq]");
+        let input = "# Squid\n[include arms.sq]\nThe end.\n[include tentacles.sq]";
+        let mut parser = BlockParser::from_string(input).resolver(resolver);
+        let blocks = parser.by_ref().map(Result::unwrap).collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                Block::Heading {
+                    level: HeadingLevel::Level1,
+                    content: vec![Inline::Chunk("Squid".into())],
+                    number: None,
+                    label: None,
+                },
+                Block::Heading {
+                    level: HeadingLevel::Level2,
+                    content: vec![Inline::Chunk("Arms".into())],
+                    number: None,
+                    label: None,
+                },
+                Block::Paragraph(vec![
+                    Inline::Chunk("Ink ".into()),
+                    Inline::Chunk("[arms][missing]".into()),
+                ]),
+                Block::Paragraph(vec![Inline::Chunk("Eight arms.".into())]),
+                Block::Paragraph(vec![Inline::Chunk("The end.".into())]),
+            ],
+            blocks
+        );
+
+        assert_eq!(
+            &[
+                Diagnostic::new(3, DiagnosticKind::IncludeCycle("../arms.sq".into()))
+                    .in_file(Some("parts/ink.sq".into())),
+                Diagnostic::new(
+                    4,
+                    DiagnosticKind::IncludeFailed("tentacles.sq".into(), "file not found".into()),
+                ),
+                Diagnostic::new(1, DiagnosticKind::UndefinedReference("missing".into()))
+                    .in_file(Some("parts/ink.sq".into())),
+            ],
+            parser.diagnostics()
+        );
+    }
+
+    #[test]
+    fn fenced_blocks_end_with_the_included_file() {
+        use resolver::MemoryResolver;
+
+        let resolver = MemoryResolver::new().file("code.sq", "[code]\n---\nlet squid;");
+        let input = "[include code.sq]\n# Squid";
+        let mut parser = BlockParser::from_string(input).resolver(resolver);
+
+        assert_eq!(
+            vec![
+                Block::Preformatted(Some(Decorator::Code(None)), "let squid;".into()),
+                Block::Heading {
+                    level: HeadingLevel::Level1,
+                    content: vec![Inline::Chunk("Squid".into())],
+                    number: None,
+                    label: None,
+                },
+            ],
+            parser.by_ref().map(Result::unwrap).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            &[Diagnostic::new(2, DiagnosticKind::UnclosedFence).in_file(Some("code.sq".into()))],
+            parser.diagnostics()
+        );
+    }
+
+    #[test]
+    fn unclosed_fences_in_included_files_dont_close_later() {
+        use resolver::MemoryResolver;
+
+        let resolver = MemoryResolver::new().file("a.sq", "[code]\n---\nx\n");
+        let mut parser = BlockParser::from_string("[include a.sq]\n---\nafter\n---")
+            .resolver(resolver);
+
+        assert_eq!(
+            vec![
+                Block::Preformatted(Some(Decorator::Code(None)), "x".into()),
+                Block::Paragraph(vec![Inline::Chunk("after".into())]),
+            ],
+            parser.by_ref().map(Result::unwrap).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            &[
+                Diagnostic::new(2, DiagnosticKind::UnclosedFence).in_file(Some("a.sq".into())),
+                Diagnostic::new(2, DiagnosticKind::UnexpectedDivider),
+                Diagnostic::new(4, DiagnosticKind::UnexpectedDivider),
+            ],
+            parser.diagnostics()
+        );
+    }
+
+    #[test]
+    fn including_the_document_itself_is_a_cycle() {
+        use resolver::MemoryResolver;
+
+        let input = "# Squid
+[include arms.sq]";
+        let resolver = MemoryResolver::new()
+            .file("docs/squid.sq", input)
+            .file("docs/arms.sq", "## Arms
+[include ./squid.sq]");
+        let mut parser = BlockParser::from_string(input)
+            .resolver(resolver)
+            .path("docs/squid.sq");
+
+        assert_eq!(2, parser.by_ref().count());
+        assert_eq!(
+            &[
+                Diagnostic::new(2, DiagnosticKind::IncludeCycle("./squid.sq".into()))
+                    .in_file(Some("docs/arms.sq".into())),
+            ],
+            parser.diagnostics()
+        );
+    }
+
+    #[test]
+    fn variables_work() {
+        let input = "---\nproduct: Squid\n\nversion: 0.1\nsquid\n---\n\
+                     # {{product}} {{ version }}\n\n{{edition}}\\{{product}} {{ink}}";
+        let mut parser = BlockParser::from_string(input).variables(vec![("version", "1.0")]);
+
+        assert_eq!(
+            Block::Heading {
+                level: HeadingLevel::Level1,
+                content: vec![Inline::Chunk("Squid 1.0".into())],
+                number: None,
+                label: None,
+            },
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("{{edition}}{{product}} {{ink}}".into())]),
+            unwrap!(parser.next())
+        );
+
+        assert!(parser.next().is_none());
+
+        assert_eq!(
+            &[
+                Diagnostic::new(5, DiagnosticKind::InvalidVariableDefinition),
+                Diagnostic::new(9, DiagnosticKind::UndefinedVariable("edition".into())),
+                Diagnostic::new(9, DiagnosticKind::UndefinedVariable("ink".into())),
+            ],
+            parser.diagnostics()
+        );
+
+        let input = "// TODO\n\n---\nproduct: Squid\n---\n{{product}}";
+        let mut parser = BlockParser::from_string(input).keep_comments(true);
+
+        assert_eq!(Block::Comment("TODO".into()), unwrap!(parser.next()));
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("Squid".into())]),
+            unwrap!(parser.next())
+        );
+
+        let mut parser = BlockParser::from_string("{{product}}").keep_placeholders(true);
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("{{product}}".into())]),
+            unwrap!(parser.next())
+        );
+        assert!(parser.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn empty_targets_are_not_unresolved() {
+        let input = "[a]({{v}}) [b][x]\n\n[x]: http://x";
+        let mut parser = BlockParser::from_string(input).variables(vec![("v", "")]);
+
+        assert_eq!(
+            Block::Paragraph(vec![
+                Inline::Link {
+                    target: Some("".into()),
+                    content: vec![Inline::Chunk("a".into())],
+                },
+                Inline::Chunk(" ".into()),
+                Inline::Link {
+                    target: Some("http://x".into()),
+                    content: vec![Inline::Chunk("b".into())],
+                },
+            ]),
+            unwrap!(parser.next())
+        );
+    }
+
+    #[test]
+    fn conditionals_work() {
+        let input = "[if internal]\n---\n# Internal\n\n[if draft]\n----\nDraft\n----\n\
+                     [else]\n----\n####### Final\n----\n---\n[else]\n---\nPublic\n---\n\n\
+                     [else]\n---\nDropped\n---\nThe end.";
+        let mut parser = BlockParser::from_string(input).flags(vec!["internal"]);
+
+        assert_eq!(
+            Block::Heading {
+                level: HeadingLevel::Level1,
+                content: vec![Inline::Chunk("Internal".into())],
+                number: None,
+                label: None,
+            },
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("####### Final".into())]),
+            unwrap!(parser.next())
+        );
+
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("The end.".into())]),
+            unwrap!(parser.next())
+        );
+
+        assert!(parser.next().is_none());
+
+        assert_eq!(
+            &[
+                Diagnostic::new(11, DiagnosticKind::HeadingLevelTooDeep(7)),
+                Diagnostic::new(19, DiagnosticKind::UnexpectedElse),
+            ],
+            parser.diagnostics()
+        );
+
+        let blocks = BlockParser::from_string(input)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                Block::Paragraph(vec![Inline::Chunk("Public".into())]),
+                Block::Paragraph(vec![Inline::Chunk("The end.".into())]),
+            ],
+            blocks
+        );
+    }
+
+    #[test]
+    fn unfenced_conditions_are_dropped() {
+        let paragraph = |value: &str| Block::Paragraph(vec![Inline::Chunk(value.into())]);
+        let input = "[if internal]\nSecret paragraph\n\nPublic\n\n[else]\nOther\n\nEnd";
+
+        for flags in vec![vec![], vec!["internal"]] {
+            let mut parser = BlockParser::from_string(input).flags(flags);
+
+            assert_eq!(
+                vec![paragraph("Public"), paragraph("End")],
+                parser.by_ref().map(Result::unwrap).collect::<Vec<_>>()
+            );
+            assert_eq!(
+                &[
+                    Diagnostic::new(1, DiagnosticKind::UnfencedCondition),
+                    Diagnostic::new(6, DiagnosticKind::UnexpectedElse),
+                    Diagnostic::new(6, DiagnosticKind::UnfencedCondition),
+                ],
+                parser.diagnostics()
+            );
+        }
+
+        let input = "[if internal]\n\n---\nSecret\n---\n\nPublic";
+        let blocks = BlockParser::from_string(input)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![paragraph("Public")], blocks);
+
+        let blocks = BlockParser::from_string(input)
+            .flags(vec!["internal"])
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![paragraph("Secret"), paragraph("Public")], blocks);
+    }
+
+    #[test]
+    fn comments_work() {
+        let input = "// TODO\n# Squid\n\nInk\n/* arms */\nand tentacles\n\n\
+                     [code]\n---\n// raw\n---\n/* open";
+
+        let blocks = BlockParser::from_string(input)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+
+        assert_eq!(3, blocks.len());
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("Ink and tentacles".into())]),
+            blocks[1]
+        );
+
+        let mut parser = BlockParser::from_string(input).keep_comments(true);
+        let blocks = parser.by_ref().map(Result::unwrap).collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                Block::Comment("TODO".into()),
+                Block::Heading {
+                    level: HeadingLevel::Level1,
+                    content: vec![Inline::Chunk("Squid".into())],
+                    number: None,
+                    label: None,
+                },
+                Block::Paragraph(vec![Inline::Chunk("Ink and tentacles".into())]),
+                Block::Comment("arms".into()),
+                Block::Preformatted(Some(Decorator::Code(None)), "// raw".into()),
+                Block::Comment("open".into()),
+            ],
+            blocks
+        );
+
+        assert_eq!(&[Diagnostic::new(12, DiagnosticKind::UnclosedComment)], parser.diagnostics());
+    }
+
+    #[test]
+    fn text_after_block_comments_is_kept() {
+        let input = "/* TODO */ Intro\n\n# Heading\n\n\
+                     body\n/* arms\ntentacles */ and ink\n\n[@squid]";
+        let mut parser = BlockParser::from_string(input);
+        let blocks = parser.by_ref().map(Result::unwrap).collect::<Vec<_>>();
+
+        assert_eq!(4, blocks.len());
+        assert_eq!(Block::Paragraph(vec![Inline::Chunk("Intro".into())]), blocks[0]);
+        assert_eq!(
+            Block::Paragraph(vec![Inline::Chunk("body and ink".into())]),
+            blocks[2]
+        );
+        assert_eq!(
+            &[Diagnostic::new(9, DiagnosticKind::UndefinedLabel("squid".into()))],
+            parser.diagnostics()
         );
     }
 }
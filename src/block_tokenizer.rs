@@ -3,6 +3,8 @@ use super::tokens::{Line, LineType};
 use super::input::{ParserInputResult, IntoParserInput, IntoParserInputIter};
 use super::error::ParseError;
 use std::iter;
+use std::mem;
+use std::vec;
 use std::str::Lines;
 use std::borrow::Cow;
 
@@ -35,6 +37,44 @@ where
     I: Iterator<Item = S>,
 {
     input: iter::Peekable<IntoParserInputIter<'a, S, I>>,
+    /// The number of lines consumed from the input itself
+    input_line: usize,
+    /// The number of lines consumed in total, including the lines of included files
+    line: usize,
+    /// The spliced lines that are being read, innermost last
+    spliced: Vec<Spliced>,
+    /// The positions at which the input switched to another file
+    segments: Vec<Segment>,
+    /// Whether comments are kept instead of being dropped
+    keep_comments: bool,
+    /// The comments consumed since the last call to `take_comments`, with their position
+    comments: Vec<(usize, String)>,
+    /// The position of a block comment that is never closed
+    unclosed_comment: Option<usize>,
+}
+
+///
+/// Lines that are read before the rest of the current file,
+/// e.g. an included file or the content of a conditional block.
+///
+#[derive(Debug)]
+struct Spliced {
+    /// The file the lines belong to, `None` for the input itself
+    file: Option<String>,
+    lines: iter::Peekable<vec::IntoIter<String>>,
+    /// The number of the line in `file` that was consumed last
+    line: usize,
+    is_include: bool,
+}
+
+///
+/// Maps the lines starting at `position` to the lines of `file`, starting at `line`.
+///
+#[derive(Debug)]
+struct Segment {
+    position: usize,
+    file: Option<String>,
+    line: usize,
 }
 
 fn parse_decorator<'a>(line: &Cow<'a, str>) -> Line<'a> {
@@ -44,8 +84,131 @@ fn parse_decorator<'a>(line: &Cow<'a, str>) -> Line<'a> {
 }
 
 fn is_decorator(line: &str) -> bool {
-    line.starts_with(constants::ANNOTATION_PREFIX_TOKEN) &&
-        line.trim().ends_with(constants::ANNOTATION_SUFFIX_TOKEN)
+    let trimmed = line.trim_end();
+
+    // Brackets inside of a decorator are not allowed,
+    // which keeps lines like `[foo][bar]` (a reference link) from being decorators.
+    // Lines like `[@foo]` are cross-references.
+    let is_bracket = |c| {
+        c == constants::ANNOTATION_PREFIX_TOKEN || c == constants::ANNOTATION_SUFFIX_TOKEN
+    };
+
+    trimmed.starts_with(constants::ANNOTATION_PREFIX_TOKEN) &&
+        !trimmed.starts_with(constants::CROSS_REFERENCE_PREFIX_TOKEN) &&
+        trimmed.ends_with(constants::ANNOTATION_SUFFIX_TOKEN) && trimmed.len() >= 2 &&
+        !trimmed[1..trimmed.len() - 1].contains(is_bracket)
+}
+
+///
+/// Returns the value of a decorator line (e.g. `code rust` for `[code rust]`).
+///
+pub fn decorator_value(line: &str) -> Option<&str> {
+    if is_decorator(line) {
+        let trimmed = line.trim();
+
+        Some(&trimmed[1..trimmed.len() - 1])
+    } else {
+        None
+    }
+}
+
+///
+/// Returns the text of a line comment (e.g. `todo` for `// todo`).
+///
+fn split_line_comment(line: &str) -> Option<&str> {
+    line.strip_prefix(constants::LINE_COMMENT_TOKEN).map(str::trim)
+}
+
+///
+/// Returns the rest of a line starting a block comment (e.g. `todo` for `/* todo`).
+///
+fn split_block_comment_start(line: &str) -> Option<&str> {
+    line.strip_prefix(constants::BLOCK_COMMENT_START_TOKEN)
+}
+
+///
+/// Splits a line at the end of a block comment into the text before and after the first `*/`
+/// (e.g. `todo` and ` intro` for `todo */ intro`).
+///
+fn split_block_comment_end(line: &str) -> Option<(&str, &str)> {
+    line.find(constants::BLOCK_COMMENT_END_TOKEN).map(|index| {
+        (&line[..index], &line[index + constants::BLOCK_COMMENT_END_TOKEN.len()..])
+    })
+}
+
+///
+/// Returns the lines of spliced `content`, which never continue blocks of the current file.
+///
+fn spliced_lines(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = content.lines().map(Into::into).collect();
+
+    lines.push(String::new());
+
+    lines
+}
+
+const FOOTNOTE_LABEL_PREFIX: char = '^';
+
+fn is_label(value: &str) -> bool {
+    !value.is_empty() &&
+        !value.contains(|c: char| {
+            c.is_whitespace() || c == constants::ANNOTATION_PREFIX_TOKEN ||
+                c == constants::ANNOTATION_SUFFIX_TOKEN
+        })
+}
+
+///
+/// Splits a reference definition (`[label]: target`) into its label and target.
+///
+pub fn split_reference_definition(line: &str) -> Option<(&str, &str)> {
+    if !line.starts_with(constants::REFERENCE_DEFINITION_PREFIX_TOKEN) {
+        return None;
+    }
+
+    let end = line.find(constants::REFERENCE_DEFINITION_SUFFIX_TOKEN)?;
+    let label = &line[1..end];
+    let target = line[end + constants::REFERENCE_DEFINITION_SUFFIX_TOKEN.len()..].trim();
+
+    let is_valid = |value: &str| !value.is_empty() && !value.contains(char::is_whitespace);
+
+    if is_label(label) && !label.starts_with(FOOTNOTE_LABEL_PREFIX) && is_valid(target) {
+        Some((label, target))
+    } else {
+        None
+    }
+}
+
+///
+/// Splits a footnote definition (`[^label]: content`) into its label and content.
+///
+pub fn split_footnote_definition(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix(constants::FOOTNOTE_PREFIX_TOKEN)?;
+    let end = rest.find(constants::REFERENCE_DEFINITION_SUFFIX_TOKEN)?;
+    let label = &rest[..end];
+
+    if is_label(label) {
+        Some((label, &rest[end + constants::REFERENCE_DEFINITION_SUFFIX_TOKEN.len()..]))
+    } else {
+        None
+    }
+}
+
+fn parse_footnote_definition<'a>(line: &Cow<'a, str>) -> Option<Result<Line<'a>, ParseError>> {
+    let (label, content) = split_footnote_definition(line)?;
+
+    Some(Ok(Line::FootnoteDefinition(
+        Cow::Owned(label.into()),
+        Cow::Owned(content.into()),
+    )))
+}
+
+fn parse_reference_definition<'a>(line: &Cow<'a, str>) -> Option<Result<Line<'a>, ParseError>> {
+    let (label, target) = split_reference_definition(line)?;
+
+    Some(Ok(Line::ReferenceDefinition(
+        Cow::Owned(label.into()),
+        Cow::Owned(target.into()),
+    )))
 }
 
 fn is_divider(line: &str) -> bool {
@@ -56,6 +219,70 @@ pub fn is_blank(line: &str) -> bool {
     line.chars().all(char::is_whitespace)
 }
 
+///
+/// Splits the content of a list item into its checkbox (if any) and the remaining text.
+/// Like line starters, a checkbox only counts when followed by a space (or the end of the line).
+///
+pub fn split_checkbox(value: &str) -> (Option<bool>, &str) {
+    let unchecked = value.strip_prefix(constants::CHECKBOX_UNCHECKED_TOKEN);
+    let checked = value.strip_prefix(constants::CHECKBOX_CHECKED_TOKEN);
+
+    let (checked, rest) = match (unchecked, checked) {
+        (Some(rest), _) => (false, rest),
+        (_, Some(rest)) => (true, rest),
+        _ => return (None, value),
+    };
+
+    if rest.is_empty() {
+        (Some(checked), rest)
+    } else if let Some(rest) = rest.strip_prefix(' ') {
+        (Some(checked), rest)
+    } else {
+        (None, value)
+    }
+}
+
+///
+/// Splits an ordered list line into its explicit number (if any) and the remaining text.
+/// Returns `None` if the line is not an ordered list item.
+///
+fn split_ordered_list_marker(line: &str) -> Option<(Option<u32>, &str)> {
+    if let Some(rest) = line.strip_prefix(constants::ORDERED_LIST_TOKEN) {
+        return Some((None, rest));
+    }
+
+    let digits = line.find(|c: char| !c.is_ascii_digit())?;
+
+    if digits == 0 {
+        return None;
+    }
+
+    let rest = line[digits..].strip_prefix(constants::ORDERED_LIST_TOKEN)?;
+    let number = line[..digits].parse().ok()?;
+
+    Some((Some(number), rest))
+}
+
+fn parse_ordered_list<'a>(line: &Cow<'a, str>) -> Option<Result<Line<'a>, ParseError>> {
+    let (number, rest) = split_ordered_list_marker(line)?;
+
+    Some(Ok(Line::OrderedList(number, Cow::Owned(rest.into()))))
+}
+
+///
+/// Detects lines that look like headings but have more levels than supported (e.g. `####### foo`).
+/// Returns the number of `#`s. Such lines are treated as text.
+///
+pub fn excessive_heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|&c| c == constants::HEADING_CHAR).count();
+
+    if level > constants::MAX_HEADING_LEVEL && line[level..].starts_with(' ') {
+        Some(level)
+    } else {
+        None
+    }
+}
+
 fn get_line_type(line: &str) -> LineType {
     if is_divider(line) {
         return LineType::Divider;
@@ -65,16 +292,37 @@ fn get_line_type(line: &str) -> LineType {
         return LineType::Decorator;
     }
 
+    if split_footnote_definition(line).is_some() {
+        return LineType::FootnoteDefinition;
+    }
+
+    if split_reference_definition(line).is_some() {
+        return LineType::ReferenceDefinition;
+    }
+
     if is_blank(line) {
         return LineType::Blank;
     }
 
+    // Escaped line starters are text, the escape itself is removed by the inline parser
+    if line.starts_with(constants::ESCAPE_TOKEN) {
+        return LineType::Text;
+    }
+
     detect_line_starter!(line, constants::HEADING1_TOKEN, Heading1);
     detect_line_starter!(line, constants::HEADING2_TOKEN, Heading2);
     detect_line_starter!(line, constants::HEADING3_TOKEN, Heading3);
+    detect_line_starter!(line, constants::HEADING4_TOKEN, Heading4);
+    detect_line_starter!(line, constants::HEADING5_TOKEN, Heading5);
+    detect_line_starter!(line, constants::HEADING6_TOKEN, Heading6);
     detect_line_starter!(line, constants::QUOTE_TOKEN, Quote);
     detect_line_starter!(line, constants::UNORDERED_LIST_TOKEN, UnorderedList);
-    detect_line_starter!(line, constants::ORDERED_LIST_TOKEN, OrderedList);
+
+    if split_ordered_list_marker(line).is_some() {
+        return LineType::OrderedList;
+    }
+
+    detect_line_starter!(line, constants::DEFINITION_TOKEN, Definition);
 
     LineType::Text
 }
@@ -91,10 +339,157 @@ where
     I: Iterator<Item = S>,
 {
     pub fn new(input: I) -> Self {
-        BlockTokenizer { input: IntoParserInputIter::new(input).peekable() }
+        BlockTokenizer {
+            input: IntoParserInputIter::new(input).peekable(),
+            input_line: 0,
+            line: 0,
+            spliced: Vec::new(),
+            segments: Vec::new(),
+            keep_comments: false,
+            comments: Vec::new(),
+            unclosed_comment: None,
+        }
+    }
+
+    ///
+    /// Keeps the comments that are skipped, so they can be retrieved with `take_comments`.
+    ///
+    pub fn set_keep_comments(&mut self, enabled: bool) {
+        self.keep_comments = enabled;
+    }
+
+    ///
+    /// Returns the comments consumed since the last call, with their position,
+    /// if comments are kept.
+    ///
+    pub fn take_comments(&mut self) -> Vec<(usize, String)> {
+        mem::take(&mut self.comments)
+    }
+
+    ///
+    /// Returns the position of a block comment that is never closed, once.
+    ///
+    pub fn take_unclosed_comment(&mut self) -> Option<usize> {
+        self.unclosed_comment.take()
+    }
+
+    ///
+    /// Returns the position of the line that was consumed last, starting at 1.
+    /// Without included files, this is the number of the line.
+    ///
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    ///
+    /// Returns the file (`None` for the input itself) and the number of the line at `position`.
+    ///
+    pub fn locate(&self, position: usize) -> (Option<&str>, usize) {
+        match self.segments.iter().rev().find(|segment| segment.position <= position) {
+            Some(segment) => (segment.file.as_deref(), segment.line + position - segment.position),
+            None => (None, position),
+        }
+    }
+
+    ///
+    /// The names of the included files that are being read, innermost last.
+    ///
+    pub fn includes(&self) -> impl Iterator<Item = &str> {
+        self.spliced
+            .iter()
+            .filter(|spliced| spliced.is_include)
+            .filter_map(|spliced| spliced.file.as_deref())
+    }
+
+    ///
+    /// The number of included files and spliced contents that are being read.
+    ///
+    pub fn spliced_depth(&mut self) -> usize {
+        self.has_spliced();
+        self.spliced.len()
+    }
+
+    ///
+    /// Continues with the lines of the file `name` before the rest of the current file.
+    ///
+    pub fn include(&mut self, name: String, content: &str) {
+        self.push_spliced(Some(name), 1, spliced_lines(content), true);
     }
 
+    ///
+    /// Continues with `content` before the rest of the current file,
+    /// e.g. the content of a fenced block that should be parsed.
+    /// `position` is the position of the first line of `content`.
+    ///
+    pub fn splice(&mut self, position: usize, content: &str) {
+        let (file, line) = self.locate(position);
+        let file = file.map(String::from);
+
+        self.push_spliced(file, line, spliced_lines(content), false);
+    }
+
+    fn push_spliced(
+        &mut self,
+        file: Option<String>,
+        line: usize,
+        lines: Vec<String>,
+        is_include: bool,
+    ) {
+        self.segments.push(Segment {
+            position: self.line + 1,
+            file: file.clone(),
+            line,
+        });
+
+        self.spliced.push(Spliced {
+            file,
+            lines: lines.into_iter().peekable(),
+            line: line - 1,
+            is_include,
+        });
+    }
+
+    ///
+    /// Returns to the current file once the innermost spliced lines have been read.
+    /// Returns `false` if there are no spliced lines left.
+    ///
+    fn has_spliced(&mut self) -> bool {
+        while let Some(spliced) = self.spliced.last_mut() {
+            if spliced.lines.peek().is_some() {
+                return true;
+            }
+
+            self.spliced.pop();
+
+            let (file, line) = match self.spliced.last() {
+                Some(spliced) => (spliced.file.clone(), spliced.line),
+                None => (None, self.input_line),
+            };
+
+            self.segments.push(Segment {
+                position: self.line + 1,
+                file,
+                line: line + 1,
+            });
+        }
+
+        false
+    }
+
+    ///
+    /// Returns the type of the next line, skipping comments.
+    /// Lines consumed with `consume_raw` (e.g. the content of fenced blocks) are not skipped.
+    ///
     pub fn peek(&mut self) -> Option<Result<LineType, PeekError>> {
+        self.skip_comments();
+
+        if self.has_spliced() {
+            // has_spliced() makes sure that there is a line
+            let line = self.spliced.last_mut().unwrap().lines.peek().unwrap();
+
+            return Some(Ok(get_line_type(line)));
+        }
+
         let result = match *self.input.peek()? {
             Err(_) => Err(PeekError),
             Ok(ref line) => Ok(get_line_type(line)),
@@ -103,6 +498,94 @@ where
         Some(result)
     }
 
+    ///
+    /// Returns the next line without consuming it, skipping comments like `peek`.
+    /// Returns `None` if the line is an error.
+    ///
+    pub fn peek_raw(&mut self) -> Option<&str> {
+        self.skip_comments();
+        self.peek_line()
+    }
+
+    fn skip_comments(&mut self) {
+        loop {
+            let position = self.line + 1;
+            let line = match self.peek_line() {
+                Some(line) => line,
+                None => return,
+            };
+
+            let comment = if let Some(text) = split_line_comment(line) {
+                let text = text.to_string();
+
+                self.consume_raw();
+
+                text
+            } else if split_block_comment_start(line).is_some() {
+                self.consume_block_comment()
+            } else {
+                return;
+            };
+
+            if self.keep_comments {
+                self.comments.push((position, comment));
+            }
+        }
+    }
+
+    ///
+    /// Consumes a block comment, which ends at the first `*/`, and returns its text.
+    /// The text following `*/` is read as the next line.
+    ///
+    fn consume_block_comment(&mut self) -> String {
+        let start = self.line + 1;
+        let mut lines: Vec<String> = Vec::new();
+
+        while let Some(line) = self.peek_line() {
+            let line = if lines.is_empty() {
+                &line[constants::BLOCK_COMMENT_START_TOKEN.len()..]
+            } else {
+                line
+            };
+
+            let end = split_block_comment_end(line)
+                .map(|(text, rest)| (text.to_string(), rest.trim_start().to_string()));
+
+            if let Some((text, rest)) = end {
+                lines.push(text);
+                self.consume_raw();
+
+                if !rest.trim().is_empty() {
+                    // The rest continues the line's file without a blank line after it
+                    let (file, line) = self.locate(self.line);
+                    let file = file.map(String::from);
+
+                    self.push_spliced(file, line, vec![rest], false);
+                }
+
+                return lines.join("\n").trim().into();
+            }
+
+            lines.push(line.into());
+            self.consume_raw();
+        }
+
+        self.unclosed_comment = Some(start);
+
+        lines.join("\n").trim().into()
+    }
+
+    fn peek_line(&mut self) -> Option<&str> {
+        if self.has_spliced() {
+            return self.spliced.last_mut()?.lines.peek().map(String::as_str);
+        }
+
+        match *self.input.peek()? {
+            Ok(ref line) => Some(line),
+            Err(_) => None,
+        }
+    }
+
     pub fn consume(&mut self, line_type: LineType) -> Option<Result<Line<'a>, ParseError>> {
         match self.consume_raw()? {
             Err(err) => Some(Err(err)),
@@ -115,20 +598,40 @@ where
                     LineType::Heading1 => parse_starter!(line, constants::HEADING1_TOKEN, Heading1),
                     LineType::Heading2 => parse_starter!(line, constants::HEADING2_TOKEN, Heading2),
                     LineType::Heading3 => parse_starter!(line, constants::HEADING3_TOKEN, Heading3),
+                    LineType::Heading4 => parse_starter!(line, constants::HEADING4_TOKEN, Heading4),
+                    LineType::Heading5 => parse_starter!(line, constants::HEADING5_TOKEN, Heading5),
+                    LineType::Heading6 => parse_starter!(line, constants::HEADING6_TOKEN, Heading6),
                     LineType::Quote => parse_starter!(line, constants::QUOTE_TOKEN, Quote),
                     LineType::UnorderedList => {
                         parse_starter!(line, constants::UNORDERED_LIST_TOKEN, UnorderedList)
                     }
-                    LineType::OrderedList => {
-                        parse_starter!(line, constants::ORDERED_LIST_TOKEN, OrderedList)
+                    LineType::OrderedList => parse_ordered_list(&line),
+                    LineType::Definition => {
+                        parse_starter!(line, constants::DEFINITION_TOKEN, Definition)
                     }
+                    LineType::ReferenceDefinition => parse_reference_definition(&line),
+                    LineType::FootnoteDefinition => parse_footnote_definition(&line),
                 }
             }
         }
     }
 
     pub fn consume_raw(&mut self) -> Option<ParserInputResult<'a>> {
-        self.input.next()
+        if self.has_spliced() {
+            let spliced = self.spliced.last_mut().unwrap();
+
+            spliced.line += 1;
+            self.line += 1;
+
+            return spliced.lines.next().map(|line| Ok(Cow::Owned(line)));
+        }
+
+        let line = self.input.next()?;
+
+        self.input_line += 1;
+        self.line += 1;
+
+        Some(line)
     }
 
     #[allow(dead_code)]
@@ -203,6 +706,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn heading_4_to_6_works() {
+        let mut tokenizer = BlockTokenizer::from_string("#### four\n##### five\n###### six");
+
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Heading4("four".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Heading5("five".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Heading6("six".into())
+        );
+    }
+
+    #[test]
+    fn heading_7_is_text() {
+        let mut tokenizer = BlockTokenizer::from_string("####### seven");
+
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text("####### seven".into())
+        );
+        assert_eq!(Some(7), excessive_heading_level("####### seven"));
+        assert_eq!(None, excessive_heading_level("###### six"));
+        assert_eq!(None, excessive_heading_level("#######seven"));
+    }
+
     #[test]
     fn text_with_hash_works() {
         let mut tokenizer = BlockTokenizer::from_string(" # lorem ipsum");
@@ -213,6 +747,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn escaped_line_starters_are_text() {
+        let mut tokenizer = BlockTokenizer::from_string("\\# hello\n\\- item\n\\[code]\n\\---");
+
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text("\\# hello".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text("\\- item".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text("\\[code]".into())
+        );
+        assert_eq!(unwrap!(tokenizer.consume_line()), Line::Text("\\---".into()));
+    }
+
     #[test]
     fn quote_works() {
         let mut tokenizer = BlockTokenizer::from_string("> quote\n > quote\n>quote");
@@ -233,8 +786,9 @@ mod tests {
 
     #[test]
     fn decorator_works() {
-        let mut tokenizer =
-            BlockTokenizer::from_string("[code]\n[code]   \n [code] \n  [code]  \n[code");
+        let mut tokenizer = BlockTokenizer::from_string(
+            "[code]\n[code]   \n [code] \n  [code]  \n[code\n[foo][bar]\n[@foo]",
+        );
 
         assert_eq!(
             unwrap!(tokenizer.consume_line()),
@@ -256,6 +810,57 @@ mod tests {
             unwrap!(tokenizer.consume_line()),
             Line::Text("[code".into())
         );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text("[foo][bar]".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text("[@foo]".into())
+        );
+    }
+
+    #[test]
+    fn reference_definition_works() {
+        let mut tokenizer = BlockTokenizer::from_string(
+            "[squid]: https://squid.rs  \n[]: foo\n[a b]: foo\n[foo]: a b\n [foo]: bar",
+        );
+
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::ReferenceDefinition("squid".into(), "https://squid.rs".into())
+        );
+        assert_eq!(unwrap!(tokenizer.consume_line()), Line::Text("[]: foo".into()));
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text("[a b]: foo".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text("[foo]: a b".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text(" [foo]: bar".into())
+        );
+    }
+
+    #[test]
+    fn footnote_definition_works() {
+        let mut tokenizer = BlockTokenizer::from_string("[^1]: some text\n[^note]: \n[^a b]: foo");
+
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::FootnoteDefinition("1".into(), "some text".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::FootnoteDefinition("note".into(), "".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text("[^a b]: foo".into())
+        );
     }
 
     #[test]
@@ -276,13 +881,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn definition_works() {
+        let mut tokenizer = BlockTokenizer::from_string(": meaning\n :not\n\\: escaped");
+
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Definition("meaning".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text(" :not".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text("\\: escaped".into())
+        );
+    }
+
     #[test]
     fn ordered_list_works() {
         let mut tokenizer = BlockTokenizer::from_string(".  item\n . item\n.item");
 
         assert_eq!(
             unwrap!(tokenizer.consume_line()),
-            Line::OrderedList(" item".into())
+            Line::OrderedList(None, " item".into())
         );
         assert_eq!(
             unwrap!(tokenizer.consume_line()),
@@ -294,6 +917,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn split_checkbox_works() {
+        assert_eq!((Some(false), "do"), split_checkbox("[ ] do"));
+        assert_eq!((Some(true), "this"), split_checkbox("[x] this"));
+        assert_eq!((Some(true), ""), split_checkbox("[x]"));
+        assert_eq!((None, "[x]this"), split_checkbox("[x]this"));
+        assert_eq!((None, "[X] that"), split_checkbox("[X] that"));
+        assert_eq!((None, "and that"), split_checkbox("and that"));
+    }
+
+    #[test]
+    fn numbered_ordered_list_works() {
+        let mut tokenizer =
+            BlockTokenizer::from_string("3. item\n10.  item\n3.item\n 3. item\n-3. item");
+
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::OrderedList(Some(3), "item".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::OrderedList(Some(10), " item".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text("3.item".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text(" 3. item".into())
+        );
+        assert_eq!(
+            unwrap!(tokenizer.consume_line()),
+            Line::Text("-3. item".into())
+        );
+    }
+
     #[test]
     fn divider_works() {
         let mut tokenizer = BlockTokenizer::from_string("---\n------- \n--\n ---\n---foobar");
@@ -308,6 +968,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn includes_work() {
+        let mut tokenizer = BlockTokenizer::from_string("squid\nink");
+
+        assert_eq!(unwrap!(tokenizer.consume_line()), Line::Text("squid".into()));
+
+        tokenizer.include("arms.sq".into(), "# arms\ntentacles");
+
+        assert_eq!(vec!["arms.sq"], tokenizer.includes().collect::<Vec<_>>());
+        assert_eq!(unwrap!(tokenizer.consume_line()), Line::Heading1("arms".into()));
+        assert_eq!(unwrap!(tokenizer.consume_line()), Line::Text("tentacles".into()));
+        assert_eq!(unwrap!(tokenizer.consume_line()), Line::Blank);
+        assert_eq!(unwrap!(tokenizer.consume_line()), Line::Text("ink".into()));
+        assert!(tokenizer.consume_line().is_none());
+        assert_eq!(0, tokenizer.includes().count());
+
+        assert_eq!((None, 1), tokenizer.locate(1));
+        assert_eq!((Some("arms.sq"), 2), tokenizer.locate(3));
+        assert_eq!((None, 2), tokenizer.locate(5));
+    }
+
+    #[test]
+    fn splicing_works() {
+        let mut tokenizer = BlockTokenizer::from_string("[if squid]\n---\nink\n---\narms");
+
+        for _ in 0..4 {
+            tokenizer.consume_line();
+        }
+
+        tokenizer.splice(3, "ink");
+
+        assert_eq!(0, tokenizer.includes().count());
+        assert_eq!(Some("ink"), tokenizer.peek_raw());
+        assert_eq!(unwrap!(tokenizer.consume_line()), Line::Text("ink".into()));
+        assert_eq!(unwrap!(tokenizer.consume_line()), Line::Blank);
+        assert_eq!(Some("arms"), tokenizer.peek_raw());
+        assert_eq!((None, 3), tokenizer.locate(5));
+        assert_eq!((None, 5), tokenizer.locate(7));
+        assert_eq!(Some("if squid"), decorator_value("[if squid] "));
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let mut tokenizer =
+            BlockTokenizer::from_string("// squid\nink\n/* arms\n  tentacles */\n/*\n// raw");
+
+        tokenizer.set_keep_comments(true);
+
+        assert_eq!(unwrap!(tokenizer.consume_line()), Line::Text("ink".into()));
+        assert_eq!(vec![(1, "squid".into())], tokenizer.take_comments());
+        assert!(tokenizer.consume_line().is_none());
+        assert_eq!(
+            vec![(3, "arms\n  tentacles".into()), (5, "// raw".into())],
+            tokenizer.take_comments()
+        );
+        assert_eq!(Some(5), tokenizer.take_unclosed_comment());
+    }
+
     #[test]
     fn empty_works() {
         let mut tokenizer = BlockTokenizer::from_string("   \t");
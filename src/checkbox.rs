@@ -0,0 +1,188 @@
+use super::ast::{Block, Span};
+use super::block_parser::BlockParser;
+use super::constants;
+use super::error::ParseError;
+
+///
+/// Returns the locations of the checkboxes in `block`, in the order they are rendered.
+///
+fn checkbox_spans(block: Block) -> Vec<Span> {
+    match block {
+        Block::List(_, items) => items.into_iter().filter_map(|item| item.checkbox_span).collect(),
+        Block::Figure { body, .. } => checkbox_spans(*body),
+        _ => Vec::new(),
+    }
+}
+
+///
+/// Toggles the checkbox of a task list item and returns the modified source.
+///
+/// `position` is the zero-based index of the checkbox, counting all checkboxes
+/// of the document in order of appearance (which is also the order in which they are rendered).
+/// Everything besides the checkbox is emitted unchanged.
+/// Returns `None` if the document contains no checkbox at the given position.
+///
+/// # Example
+///
+/// ```
+/// use squid::toggle_checkbox;
+///
+/// let source = "- [ ] do\n- [x] this\n";
+///
+/// assert_eq!(Some("- [x] do\n- [x] this\n".into()), toggle_checkbox(source, 0));
+/// assert_eq!(Some("- [ ] do\n- [ ] this\n".into()), toggle_checkbox(source, 1));
+/// assert_eq!(None, toggle_checkbox(source, 2));
+/// ```
+///
+pub fn toggle_checkbox(input: &str, position: usize) -> Option<String> {
+    toggle_checkbox_in(input, BlockParser::from_string(input), position)
+}
+
+///
+/// Toggles a checkbox like `toggle_checkbox`, counting the checkboxes of `blocks`,
+/// which are the blocks of `input` (e.g. parsed with the flags the document is rendered with).
+/// Checkboxes of included files are counted, but can't be toggled in `input`.
+///
+/// # Example
+///
+/// ```
+/// use squid::{toggle_checkbox_in, BlockParser};
+///
+/// let source = "[if draft]\n---\n- [ ] draft\n---\n- [ ] final";
+/// let parser = BlockParser::from_string(source).flags(vec!["draft"]);
+///
+/// assert_eq!(
+///     Some("[if draft]\n---\n- [x] draft\n---\n- [ ] final".into()),
+///     toggle_checkbox_in(source, parser, 0)
+/// );
+/// ```
+///
+pub fn toggle_checkbox_in<B>(input: &str, blocks: B, position: usize) -> Option<String>
+where
+    B: IntoIterator<Item = Result<Block, ParseError>>,
+{
+    let span = blocks
+        .into_iter()
+        .filter_map(Result::ok)
+        .flat_map(checkbox_spans)
+        .nth(position)?;
+
+    if span.file.is_some() {
+        return None;
+    }
+
+    let offset: usize = input
+        .split('\n')
+        .take(span.line - 1)
+        .map(|line| line.len() + 1)
+        .sum();
+    let start = offset + span.range.start;
+    let end = offset + span.range.end;
+
+    let replacement = match input.get(start..end)? {
+        constants::CHECKBOX_UNCHECKED_TOKEN => constants::CHECKBOX_CHECKED_TOKEN,
+        constants::CHECKBOX_CHECKED_TOKEN => constants::CHECKBOX_UNCHECKED_TOKEN,
+        // The line was changed before it was parsed (e.g. the rest of a line after a comment)
+        _ => return None,
+    };
+
+    let mut output = String::with_capacity(input.len());
+
+    output.push_str(&input[..start]);
+    output.push_str(replacement);
+    output.push_str(&input[end..]);
+
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::resolver::MemoryResolver;
+
+    #[test]
+    fn toggle_checkbox_works() {
+        let source = "# Todo\r\n\r\n. [x] uno\r\n. due\r\n\r\n- [ ] do\r\n12. [ ] tres";
+
+        assert_eq!(
+            Some("# Todo\r\n\r\n. [ ] uno\r\n. due\r\n\r\n- [ ] do\r\n12. [ ] tres".into()),
+            toggle_checkbox(source, 0)
+        );
+
+        assert_eq!(
+            Some("# Todo\r\n\r\n. [x] uno\r\n. due\r\n\r\n- [x] do\r\n12. [ ] tres".into()),
+            toggle_checkbox(source, 1)
+        );
+
+        assert_eq!(
+            Some("# Todo\r\n\r\n. [x] uno\r\n. due\r\n\r\n- [ ] do\r\n12. [x] tres".into()),
+            toggle_checkbox(source, 2)
+        );
+    }
+
+    #[test]
+    fn only_checkboxes_in_lists_are_toggled() {
+        let source = "[ ] not a list\n- [ ]not a checkbox\n-  [ ] not a checkbox either";
+
+        assert_eq!(None, toggle_checkbox(source, 0));
+    }
+
+    #[test]
+    fn fenced_blocks_are_skipped() {
+        let source = "[math]\n---\n- [ ] fenced\n---\n- [ ] item";
+
+        assert_eq!(
+            Some("[math]\n---\n- [ ] fenced\n---\n- [x] item".into()),
+            toggle_checkbox(source, 0)
+        );
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let source = "// - [ ] line\n/* start\n- [ ] block */\n/* - [ ] inline */\n- [ ] item";
+
+        assert_eq!(
+            Some("// - [ ] line\n/* start\n- [ ] block */\n/* - [ ] inline */\n- [x] item".into()),
+            toggle_checkbox(source, 0)
+        );
+    }
+
+    #[test]
+    fn conditional_blocks_are_skipped_unless_parsed() {
+        let source = "[if draft]\n---\n- [ ] draft\n---\n[else]\n---\n- [ ] final\n---\n- [ ] item";
+        let toggled = |line: &str| source.replacen(line, &line.replace("[ ]", "[x]"), 1);
+        let draft = || BlockParser::from_string(source).flags(vec!["draft"]);
+
+        assert_eq!(Some(toggled("- [ ] draft")), toggle_checkbox_in(source, draft(), 0));
+        assert_eq!(Some(toggled("- [ ] item")), toggle_checkbox_in(source, draft(), 1));
+        assert_eq!(Some(toggled("- [ ] final")), toggle_checkbox(source, 0));
+        assert_eq!(Some(toggled("- [ ] item")), toggle_checkbox(source, 1));
+        assert_eq!(None, toggle_checkbox(source, 2));
+    }
+
+    #[test]
+    fn included_checkboxes_are_counted() {
+        let source = "- [ ] do\n\n[include todo.sq]\n\n- [ ] this";
+        let parser = || {
+            let resolver = MemoryResolver::new().file("todo.sq", "- [ ] that");
+
+            BlockParser::from_string(source).resolver(resolver)
+        };
+
+        assert_eq!(None, toggle_checkbox_in(source, parser(), 1));
+        assert_eq!(
+            Some("- [ ] do\n\n[include todo.sq]\n\n- [x] this".into()),
+            toggle_checkbox_in(source, parser(), 2)
+        );
+    }
+
+    #[test]
+    fn escapes_are_preserved() {
+        let source = "- \\[x] escaped\n- [x] \\[x] checked";
+
+        assert_eq!(
+            Some("- \\[x] escaped\n- [ ] \\[x] checked".into()),
+            toggle_checkbox(source, 0)
+        );
+    }
+}
@@ -1,9 +1,40 @@
 pub const HEADING1_TOKEN: &str = "# ";
 pub const HEADING2_TOKEN: &str = "## ";
 pub const HEADING3_TOKEN: &str = "### ";
+pub const HEADING4_TOKEN: &str = "#### ";
+pub const HEADING5_TOKEN: &str = "##### ";
+pub const HEADING6_TOKEN: &str = "###### ";
+pub const HEADING_CHAR: char = '#';
+pub const MAX_HEADING_LEVEL: usize = 6;
+pub const MAX_INCLUDE_DEPTH: usize = 16;
 pub const QUOTE_TOKEN: &str = "> ";
 pub const UNORDERED_LIST_TOKEN: &str = "- ";
 pub const ORDERED_LIST_TOKEN: &str = ". ";
+pub const DEFINITION_TOKEN: &str = ": ";
+
+pub const ESCAPE_TOKEN: char = '\\';
+
+pub const LINE_COMMENT_TOKEN: &str = "//";
+pub const BLOCK_COMMENT_START_TOKEN: &str = "/*";
+pub const BLOCK_COMMENT_END_TOKEN: &str = "*/";
 
 pub const ANNOTATION_PREFIX_TOKEN: char = '[';
 pub const ANNOTATION_SUFFIX_TOKEN: char = ']';
+
+pub const REFERENCE_DEFINITION_PREFIX_TOKEN: char = '[';
+pub const FOOTNOTE_PREFIX_TOKEN: &str = "[^";
+pub const CROSS_REFERENCE_PREFIX_TOKEN: &str = "[@";
+pub const VARIABLE_PREFIX_TOKEN: &str = "{{";
+pub const VARIABLE_SUFFIX_TOKEN: &str = "}}";
+pub const VARIABLE_DEFINITION_TOKEN: char = ':';
+pub const REFERENCE_DEFINITION_SUFFIX_TOKEN: &str = "]: ";
+
+pub const SECTION_ID_PREFIX: &str = "sec-";
+pub const FOOTNOTE_ID_PREFIX: &str = "fn-";
+pub const FOOTNOTE_REFERENCE_ID_PREFIX: &str = "fnref-";
+// Labels are used as ids, so they can't start like generated ids
+pub const RESERVED_LABEL_PREFIXES: &[&str] =
+    &[SECTION_ID_PREFIX, FOOTNOTE_ID_PREFIX, FOOTNOTE_REFERENCE_ID_PREFIX];
+
+pub const CHECKBOX_UNCHECKED_TOKEN: &str = "[ ]";
+pub const CHECKBOX_CHECKED_TOKEN: &str = "[x]";
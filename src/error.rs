@@ -1,5 +1,6 @@
 use std::error::Error;
 use std::fmt;
+use super::constants;
 
 #[derive(Debug)]
 pub enum ParseError {
@@ -38,6 +39,216 @@ impl Error for ParseError {
     }
 }
 
+///
+/// A problem in the input that does not prevent it from being parsed.
+/// Squid degrades gracefully, so these are collected by the parser
+/// instead of being returned as errors.
+///
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Diagnostic {
+    file: Option<String>,
+    line: usize,
+    kind: DiagnosticKind,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// A line starting with more `#`s than there are heading levels.
+    /// Contains the number of `#`s. The line is treated as text.
+    HeadingLevelTooDeep(usize),
+    /// A reference link or image whose label is never defined.
+    /// Contains the label. The markup is treated as text.
+    UndefinedReference(String),
+    /// A second definition for the same label. Contains the label.
+    /// The first definition is used.
+    DuplicateReferenceDefinition(String),
+    /// A footnote reference whose label is never defined. Contains the label.
+    UndefinedFootnote(String),
+    /// A second definition for the same footnote. Contains the label.
+    /// The second definition is dropped.
+    DuplicateFootnoteDefinition(String),
+    /// A footnote that is defined but never referenced. Contains the label.
+    UnreferencedFootnote(String),
+    /// A fenced block whose closing divider is missing.
+    /// The block extends to the end of the input, or of the included file it starts in.
+    UnclosedFence,
+    /// A cross-reference to a label that is never defined.
+    /// Contains the label. The markup is treated as text.
+    UndefinedLabel(String),
+    /// A second block with the same label. Contains the label.
+    /// The label of the second block is dropped.
+    DuplicateLabel(String),
+    /// A label starting like the ids generated for sections and footnotes (e.g. `fn-1`).
+    /// Contains the label. The label is dropped.
+    ReservedLabel(String),
+    /// A label on a block that can't be labeled (only headings and figures can).
+    /// Contains the label. The label is dropped.
+    UnusedLabel(String),
+    /// A file that can't be included. Contains the path and the reason.
+    IncludeFailed(String, String),
+    /// A file that includes itself, directly or through other files. Contains the path.
+    /// The file is not included again.
+    IncludeCycle(String),
+    /// Files that are nested deeper than the include depth limit. Contains the path.
+    IncludeTooDeep(String),
+    /// A placeholder for a variable that is never defined. Contains the name.
+    /// The placeholder is treated as text.
+    UndefinedVariable(String),
+    /// A line in the front matter that doesn't define a variable (`name: value`).
+    /// The line is ignored.
+    InvalidVariableDefinition,
+    /// An `[if]` or `[else]` decorator that isn't followed by a fenced block.
+    /// The lines up to the next blank line are dropped, whether the flag is set or not.
+    UnfencedCondition,
+    /// A `[figure]` decorator without a block that can be shown directly after it
+    /// (e.g. followed by a footnote definition or the end of the input). The decorator is ignored.
+    FigureWithoutBody,
+    /// An `[else]` block that doesn't directly follow an `[if]` block. The block is dropped.
+    UnexpectedElse,
+    /// A divider that neither follows a decorator nor starts the front matter.
+    /// The line is ignored.
+    UnexpectedDivider,
+    /// A block comment whose closing `*/` is missing.
+    /// The comment extends to the end of the input.
+    UnclosedComment,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(line: usize, kind: DiagnosticKind) -> Self {
+        Diagnostic {
+            file: None,
+            line,
+            kind,
+        }
+    }
+
+    pub(crate) fn in_file(mut self, file: Option<String>) -> Self {
+        self.file = file;
+
+        self
+    }
+
+    ///
+    /// The name of the included file the diagnostic refers to,
+    /// or `None` for the document itself.
+    ///
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    ///
+    /// The line the diagnostic refers to, starting at 1.
+    ///
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn kind(&self) -> &DiagnosticKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            write!(f, "{}, ", file)?;
+        }
+
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DiagnosticKind::HeadingLevelTooDeep(level) => {
+                write!(
+                    f,
+                    "headings have at most {} levels, found {}; the line is treated as text",
+                    constants::MAX_HEADING_LEVEL,
+                    level
+                )
+            }
+            DiagnosticKind::UndefinedReference(ref label) => {
+                write!(f, "reference `{}` is not defined", label)
+            }
+            DiagnosticKind::DuplicateReferenceDefinition(ref label) => {
+                write!(f, "reference `{}` is already defined", label)
+            }
+            DiagnosticKind::UndefinedFootnote(ref label) => {
+                write!(f, "footnote `{}` is not defined", label)
+            }
+            DiagnosticKind::DuplicateFootnoteDefinition(ref label) => {
+                write!(f, "footnote `{}` is already defined; the definition is dropped", label)
+            }
+            DiagnosticKind::UnreferencedFootnote(ref label) => {
+                write!(f, "footnote `{}` is never referenced", label)
+            }
+            DiagnosticKind::UnclosedFence => {
+                write!(f, "the block is never closed and extends to the end of the file")
+            }
+            DiagnosticKind::UndefinedLabel(ref label) => {
+                write!(f, "label `{}` is not defined", label)
+            }
+            DiagnosticKind::DuplicateLabel(ref label) => {
+                write!(f, "label `{}` is already used; the label is dropped", label)
+            }
+            DiagnosticKind::ReservedLabel(ref label) => {
+                write!(f, "label `{}` is reserved for generated ids; the label is dropped", label)
+            }
+            DiagnosticKind::UnusedLabel(ref label) => {
+                write!(
+                    f,
+                    "label `{}` must be followed by a heading or a figure; the label is dropped",
+                    label
+                )
+            }
+            DiagnosticKind::IncludeFailed(ref path, ref reason) => {
+                write!(f, "`{}` can't be included: {}", path, reason)
+            }
+            DiagnosticKind::IncludeCycle(ref path) => {
+                write!(f, "`{}` includes itself and is not included again", path)
+            }
+            DiagnosticKind::IncludeTooDeep(ref path) => {
+                write!(
+                    f,
+                    "includes are nested at most {} levels deep; `{}` is not included",
+                    constants::MAX_INCLUDE_DEPTH,
+                    path
+                )
+            }
+            DiagnosticKind::UndefinedVariable(ref name) => {
+                write!(f, "variable `{}` is not defined", name)
+            }
+            DiagnosticKind::InvalidVariableDefinition => {
+                write!(f, "expected a variable definition (`name: value`); the line is ignored")
+            }
+            DiagnosticKind::UnfencedCondition => {
+                write!(
+                    f,
+                    "`[if]` and `[else]` must be followed by a fenced block; the block is dropped"
+                )
+            }
+            DiagnosticKind::FigureWithoutBody => {
+                write!(
+                    f,
+                    "`[figure]` must be directly followed by a block; the decorator is ignored"
+                )
+            }
+            DiagnosticKind::UnexpectedElse => {
+                write!(f, "`[else]` must directly follow an `[if]` block; the block is dropped")
+            }
+            DiagnosticKind::UnexpectedDivider => {
+                write!(f, "dividers must follow a decorator like `[code]`; the line is ignored")
+            }
+            DiagnosticKind::UnclosedComment => {
+                write!(f, "the comment is never closed and extends to the end of the input")
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,4 +269,22 @@ mod tests {
 
         assert_eq!(format!("{}", io_err), format!("{}", err));
     }
+
+    #[test]
+    fn diagnostic_display_works() {
+        let diagnostic = Diagnostic::new(3, DiagnosticKind::HeadingLevelTooDeep(7));
+
+        assert_eq!(
+            "line 3: headings have at most 6 levels, found 7; the line is treated as text",
+            format!("{}", diagnostic)
+        );
+
+        let diagnostic = Diagnostic::new(1, DiagnosticKind::IncludeCycle("squid.sq".into()))
+            .in_file(Some("ink.sq".into()));
+
+        assert_eq!(
+            "ink.sq, line 1: `squid.sq` includes itself and is not included again",
+            format!("{}", diagnostic)
+        );
+    }
 }
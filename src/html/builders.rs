@@ -1,12 +1,25 @@
 use std::fmt;
 use std::mem;
 use std::borrow::Cow;
-use super::output::Output;
+use super::output::{Output, OutputMode};
 use super::escape::Escape;
+use super::renderer::RenderError;
+
+///
+/// Elements without content, which have no end tag.
+///
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
 
 #[derive(Debug)]
 pub struct Builder {
     events: Vec<Event>,
+    /// Elements that have been started but not ended yet, innermost last
+    open: Vec<Cow<'static, str>>,
+    /// The first error, which is returned when the builder is consumed
+    error: Option<RenderError>,
 }
 
 #[derive(Debug)]
@@ -27,13 +40,65 @@ pub(crate) enum Event {
         name: Cow<'static, str>,
         attrs: Vec<Attribute<'static>>,
     },
+    /// An element without content or end tag (e.g. `<br>`)
+    VoidTag {
+        name: Cow<'static, str>,
+        attrs: Vec<Attribute<'static>>,
+    },
     Text { text: Cow<'static, str> },
     TagEnd { name: Cow<'static, str> },
 }
 
-fn format_attrs<'a>(f: &mut fmt::Formatter, attrs: &[Attribute<'a>]) -> fmt::Result {
+///
+/// Whether `name` is a valid tag name. Custom elements (e.g. `squid-ink`) are allowed.
+///
+fn is_valid_tag_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic()) &&
+        name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn is_void_element(name: &str) -> bool {
+    VOID_ELEMENTS.iter().any(|element| element.eq_ignore_ascii_case(name))
+}
+
+///
+/// Whether `name` is a valid attribute name according to the HTML syntax.
+///
+fn is_valid_attribute_name(name: &str) -> bool {
+    !name.is_empty() &&
+        !name.chars().any(|c| {
+            c.is_control() || c.is_whitespace() || "\"'>/=".contains(c)
+        })
+}
+
+///
+/// Replaces characters that are not allowed in XML documents (e.g. most control characters).
+///
+fn xml_chars(value: &str) -> Cow<'_, str> {
+    let is_invalid = |c: char| {
+        (c.is_control() && c != '\t' && c != '\n' && c != '\r' && c < '\u{80}') ||
+            c == '\u{fffe}' || c == '\u{ffff}'
+    };
+
+    if value.contains(is_invalid) {
+        Cow::Owned(value.replace(is_invalid, "\u{fffd}"))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+pub(crate) fn write_escaped(f: &mut fmt::Formatter, value: &str, mode: OutputMode) -> fmt::Result {
+    match mode {
+        OutputMode::Xhtml => write!(f, "{}", Escape(&xml_chars(value))),
+        _ => write!(f, "{}", Escape(value)),
+    }
+}
+
+fn write_attrs(f: &mut fmt::Formatter, attrs: &[Attribute<'_>], mode: OutputMode) -> fmt::Result {
     for attr in attrs {
-        write!(f, " {}=\"{}\"", attr.0, Escape(&attr.1))?;
+        write!(f, " {}=\"", attr.0)?;
+        write_escaped(f, &attr.1, mode)?;
+        write!(f, "\"")?;
     }
 
     Ok(())
@@ -41,11 +106,45 @@ fn format_attrs<'a>(f: &mut fmt::Formatter, attrs: &[Attribute<'a>]) -> fmt::Res
 
 impl Builder {
     pub(crate) fn new() -> Self {
-        Builder { events: Vec::new() }
+        Builder {
+            events: Vec::new(),
+            open: Vec::new(),
+            error: None,
+        }
     }
 
-    pub(crate) fn consume(self) -> Output {
-        Output::new(self.events)
+    ///
+    /// Returns the output, closing all elements that are still open,
+    /// or the first error that occurred while building it.
+    ///
+    pub(crate) fn consume(self) -> Result<Output, RenderError> {
+        self.consume_keeping_open(0)
+    }
+
+    ///
+    /// Like `consume`, but keeps the outermost `count` open elements open,
+    /// so that they can be ended in a later output.
+    ///
+    pub(crate) fn consume_keeping_open(mut self, count: usize) -> Result<Output, RenderError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        while self.open.len() > count {
+            // unwrapping here is safe, as there are open elements left
+            let name = self.open.pop().unwrap();
+
+            self.events.push(Event::TagEnd { name });
+        }
+
+        Ok(Output::new(self.events))
+    }
+
+    ///
+    /// Whether all elements that have been started have been ended again.
+    ///
+    pub(crate) fn is_balanced(&self) -> bool {
+        self.open.is_empty()
     }
 
     pub fn tag_start<N>(&mut self, name: N) -> TagStartBuilder
@@ -68,31 +167,94 @@ impl Builder {
         self
     }
 
+    ///
+    /// Ends the innermost open element, which must be named `name`.
+    ///
     pub fn tag_end<T>(&mut self, name: T) -> &mut Self
     where
         T: Into<Cow<'static, str>>,
     {
-        self.events.push(Event::TagEnd { name: name.into() });
+        let name = name.into();
+
+        if self.open.last() == Some(&name) {
+            self.open.pop();
+            self.events.push(Event::TagEnd { name });
+        } else {
+            let expected = self.open.last().map(|open| open.to_string());
+
+            self.fail(RenderError::UnbalancedEndTag {
+                expected,
+                found: name.into_owned(),
+            });
+        }
 
         self
     }
 
-    fn append(&mut self, event: Event) {
-        self.events.push(event);
+    ///
+    /// Starts an element which is ended in a later output (e.g. a section spanning several
+    /// blocks). Unlike `tag_start`, the element is not closed when the builder is consumed.
+    ///
+    pub(crate) fn unbalanced_start(&mut self, name: &'static str, attrs: Vec<Attribute<'static>>) {
+        self.events.push(Event::TagStart {
+            name: name.into(),
+            attrs,
+        });
+    }
+
+    ///
+    /// Ends an element started in an earlier output, bypassing the balance checks.
+    ///
+    pub(crate) fn unbalanced_end<N>(&mut self, name: N)
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        self.events.push(Event::TagEnd { name: name.into() });
+    }
+
+    fn start(&mut self, name: Cow<'static, str>, attrs: Vec<Attribute<'static>>) {
+        if !is_valid_tag_name(&name) {
+            return self.fail(RenderError::InvalidName(name.into_owned()));
+        }
+
+        if is_void_element(&name) {
+            self.events.push(Event::VoidTag { name, attrs });
+        } else {
+            self.open.push(name.clone());
+            self.events.push(Event::TagStart { name, attrs });
+        }
+    }
+
+    fn fail(&mut self, error: RenderError) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
     }
 }
 
-impl<'a> fmt::Display for Event {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Event {
+    pub(crate) fn write(&self, f: &mut fmt::Formatter, mode: OutputMode) -> fmt::Result {
         match *self {
-            Event::Text { ref text } => write!(f, "{}", Escape(text)),
+            Event::Text { ref text } => write_escaped(f, text, mode),
             Event::TagStart {
                 ref name,
                 ref attrs,
             } => {
-                write!(f, "<{}", name)
-                    .and_then(|_| format_attrs(f, attrs))
-                    .and_then(|_| write!(f, ">"))
+                write!(f, "<{}", name)?;
+                write_attrs(f, attrs, mode)?;
+                write!(f, ">")
+            }
+            Event::VoidTag {
+                ref name,
+                ref attrs,
+            } => {
+                write!(f, "<{}", name)?;
+                write_attrs(f, attrs, mode)?;
+
+                match mode {
+                    OutputMode::Xhtml => write!(f, " />"),
+                    _ => write!(f, ">"),
+                }
             }
             Event::TagEnd { ref name } => write!(f, "</{}>", name),
         }
@@ -100,12 +262,23 @@ impl<'a> fmt::Display for Event {
 }
 
 impl<'a> TagStartBuilder<'a> {
+    ///
+    /// Adds an attribute to the tag.
+    /// Attributes with invalid names (e.g. containing whitespace or quotes)
+    /// are never written and result in an error.
+    ///
     pub fn add_attr<N, V>(&mut self, name: N, value: V) -> &mut Self
     where
         N: Into<Cow<'static, str>>,
         V: Into<Cow<'static, str>>,
     {
-        self.attrs.push((name.into(), value.into()));
+        let name = name.into();
+
+        if is_valid_attribute_name(&name) {
+            self.attrs.push((name, value.into()));
+        } else {
+            self.builder.fail(RenderError::InvalidName(name.into_owned()));
+        }
 
         self
     }
@@ -115,11 +288,105 @@ impl<'a> TagStartBuilder<'a> {
 
         mem::swap(&mut self.attrs, &mut attrs);
 
-        self.builder.append(Event::TagStart {
-            attrs,
-            name: self.name.clone(),
-        });
+        self.builder.start(self.name.clone(), attrs);
 
         self.builder
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_invalid_name(result: Result<Output, RenderError>, expected: &str) -> bool {
+        match result {
+            Err(RenderError::InvalidName(ref name)) => name == expected,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn open_elements_are_closed() {
+        let mut builder = Builder::new();
+
+        builder
+            .tag_start("p")
+            .finish()
+            .tag_start("br")
+            .finish()
+            .tag_start("squid-ink")
+            .finish()
+            .tag_start("img")
+            .finish();
+
+        assert_eq!(
+            "<p><br><squid-ink><img></squid-ink></p>",
+            format!("{}", builder.consume().unwrap())
+        );
+    }
+
+    #[test]
+    fn xhtml_works() {
+        let mut builder = Builder::new();
+
+        builder
+            .tag_start("p")
+            .add_attr("title", "\u{1}'\"")
+            .finish()
+            .text("a\u{0}b")
+            .tag_start("br")
+            .finish()
+            .tag_start("img")
+            .add_attr("alt", "<>")
+            .finish();
+
+        assert_eq!(
+            "<p title=\"\u{fffd}&#39;&quot;\">a\u{fffd}b<br /><img alt=\"&lt;&gt;\" /></p>",
+            format!("{}", builder.consume().unwrap().with_mode(OutputMode::Xhtml))
+        );
+    }
+
+    #[test]
+    fn unbalanced_end_tags_are_rejected() {
+        let mut builder = Builder::new();
+
+        builder.tag_start("p").finish().tag_start("a").finish().tag_end("p");
+
+        match builder.consume() {
+            Err(RenderError::UnbalancedEndTag { expected, found }) => {
+                assert_eq!((Some("a".into()), "p".into()), (expected, found));
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let mut builder = Builder::new();
+
+        builder.tag_end("p");
+
+        match builder.consume() {
+            Err(RenderError::UnbalancedEndTag { expected, found }) => {
+                assert_eq!((None, "p".into()), (expected, found));
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        for name in &["", "1p", "p onclick", "p>", "script/"] {
+            let mut builder = Builder::new();
+
+            builder.tag_start(*name).finish();
+
+            assert!(is_invalid_name(builder.consume(), name), "{:?}", name);
+        }
+
+        for name in &["", "x onclick", "\"", "a=b", "a/"] {
+            let mut builder = Builder::new();
+
+            builder.tag_start("a").add_attr(*name, "").finish();
+
+            assert!(is_invalid_name(builder.consume(), name), "{:?}", name);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use super::super::ast::{Block, Decorator};
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[non_exhaustive]
+pub enum BlockKind {
+    Heading,
+    Paragraph,
+    Quote,
+    List,
+    DefinitionList,
+    Math,
+    Code,
+    Figure,
+    /// Blocks without a dedicated kind (e.g. unknown decorated blocks)
+    Other,
+}
+
+impl BlockKind {
+    pub fn of(block: &Block) -> Self {
+        match *block {
+            Block::Heading { .. } => BlockKind::Heading,
+            Block::Paragraph(_) => BlockKind::Paragraph,
+            Block::Quote(_) => BlockKind::Quote,
+            Block::List(..) => BlockKind::List,
+            Block::DefinitionList(_) => BlockKind::DefinitionList,
+            Block::Math(_) => BlockKind::Math,
+            Block::Preformatted(Some(Decorator::Code(_)), _) => BlockKind::Code,
+            Block::Figure { .. } => BlockKind::Figure,
+            _ => BlockKind::Other,
+        }
+    }
+}
+
+///
+/// Information about the document, which is passed to every method of a `Format`.
+///
+/// # Example
+///
+/// ```
+/// use squid::BlockParser;
+/// use squid::ast::Text;
+/// use squid::html::{Format, RenderContext, Renderer};
+/// use squid::html::builders::Builder;
+///
+/// #[derive(Debug)]
+/// struct NumberedParagraphs;
+///
+/// impl Format for NumberedParagraphs {
+///     fn paragraph(&self, builder: &mut Builder, context: &mut RenderContext, text: Text) {
+///         let count = context.state::<usize>();
+///
+///         *count += 1;
+///
+///         builder.tag_start("p").add_attr("id", format!("p{}", count)).finish();
+///         self.text(builder, context, text);
+///         builder.tag_end("p");
+///     }
+/// }
+///
+/// let parser = BlockParser::from_string("squid\n\nink");
+/// let output = Renderer::with_format(NumberedParagraphs, parser)
+///     .map(|output| output.unwrap().to_string())
+///     .collect::<String>();
+///
+/// assert_eq!("<p id=\"p1\">squid</p><p id=\"p2\">ink</p>", output);
+/// ```
+///
+#[derive(Debug, Default)]
+pub struct RenderContext {
+    position: usize,
+    previous: Option<BlockKind>,
+    blocks: Vec<BlockKind>,
+    state: HashMap<TypeId, Box<dyn Any>>,
+    metadata: HashMap<String, String>,
+}
+
+impl RenderContext {
+    pub fn new() -> Self {
+        RenderContext::default()
+    }
+
+    ///
+    /// The number of top-level blocks rendered before the current one.
+    ///
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    ///
+    /// The kind of the previous top-level block, if any.
+    ///
+    pub fn previous(&self) -> Option<BlockKind> {
+        self.previous
+    }
+
+    ///
+    /// The blocks that are currently being rendered, outermost first
+    /// (e.g. a paragraph inside a definition list).
+    ///
+    pub fn blocks(&self) -> &[BlockKind] {
+        &self.blocks
+    }
+
+    ///
+    /// State shared by all blocks of the document, e.g. counters.
+    /// There is one value per type, which is created on first use.
+    ///
+    pub fn state<T>(&mut self) -> &mut T
+    where
+        T: Any + Default,
+    {
+        self.state
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(T::default()))
+            .downcast_mut()
+            // Values are always stored under the id of their type
+            .unwrap()
+    }
+
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(String::as_str)
+    }
+
+    pub fn set_metadata<K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.metadata.insert(key.into(), value.into());
+    }
+
+    pub(crate) fn enter(&mut self, kind: BlockKind) {
+        self.blocks.push(kind);
+    }
+
+    pub(crate) fn leave(&mut self) {
+        if let Some(kind) = self.blocks.pop() {
+            if self.blocks.is_empty() {
+                self.previous = Some(kind);
+                self.position += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_works() {
+        let mut context = RenderContext::new();
+
+        context.enter(BlockKind::DefinitionList);
+        context.enter(BlockKind::Paragraph);
+        *context.state::<usize>() += 2;
+
+        assert_eq!(&[BlockKind::DefinitionList, BlockKind::Paragraph], context.blocks());
+
+        context.leave();
+
+        assert_eq!((0, None), (context.position(), context.previous()));
+
+        context.leave();
+        *context.state::<usize>() += 1;
+
+        assert_eq!((1, Some(BlockKind::DefinitionList)), (context.position(), context.previous()));
+        assert_eq!(3, *context.state::<usize>());
+        assert_eq!(None, context.metadata("title"));
+    }
+}
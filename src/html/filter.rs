@@ -0,0 +1,265 @@
+//!
+//! Post-processing of the rendered HTML.
+//!
+//! Every `Output` can be turned into a stream of `HtmlEvent`s, which `Filter`s transform
+//! before the output is written (e.g. to add `target="_blank"` to all links).
+//! The transformed events are validated again, so filters can't produce invalid names
+//! or unbalanced tags. Only sections, which span several outputs, are started and ended
+//! in different outputs.
+//!
+
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+pub type Attribute = (Cow<'static, str>, Cow<'static, str>);
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Tag {
+    pub name: Cow<'static, str>,
+    pub attrs: Vec<Attribute>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum HtmlEvent {
+    /// The start of an element. Void elements (e.g. `<br>`) have no matching `End`.
+    Start(Tag),
+    /// Text content, which is escaped when the output is written
+    Text(Cow<'static, str>),
+    /// The end of the element with the given name
+    End(Cow<'static, str>),
+}
+
+///
+/// Transforms the events of each rendered block, in the order the filters were added
+/// to the `Renderer`.
+///
+/// # Example
+///
+/// ```
+/// use squid::BlockParser;
+/// use squid::html::{Filter, HtmlEvent, Renderer};
+///
+/// #[derive(Debug)]
+/// struct ExternalLinks;
+///
+/// impl Filter for ExternalLinks {
+///     fn filter(&mut self, mut event: HtmlEvent, output: &mut Vec<HtmlEvent>) {
+///         if let HtmlEvent::Start(ref mut tag) = event {
+///             if tag.name == "a" {
+///                 tag.set_attr("target", "_blank");
+///             }
+///         }
+///
+///         output.push(event);
+///     }
+/// }
+///
+/// let parser = BlockParser::from_string("[squid](https://squid.rs)");
+/// let mut renderer = Renderer::new(parser).filter(ExternalLinks);
+///
+/// assert_eq!(
+///     "<p><a href=\"https://squid.rs\" target=\"_blank\">squid</a></p>",
+///     renderer.next().unwrap().unwrap().to_string()
+/// );
+/// ```
+///
+pub trait Filter: Debug {
+    ///
+    /// Pushes the events `event` is replaced with to `output`,
+    /// e.g. nothing to remove it or several events to wrap an element.
+    ///
+    fn filter(&mut self, event: HtmlEvent, output: &mut Vec<HtmlEvent>);
+}
+
+impl Tag {
+    pub fn new<N>(name: N) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Tag {
+            name: name.into(),
+            attrs: Vec::new(),
+        }
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|attr| attr.0 == name)
+            .map(|attr| &*attr.1)
+    }
+
+    ///
+    /// Sets the value of the attribute `name`, adding it if it doesn't exist yet.
+    ///
+    pub fn set_attr<N, V>(&mut self, name: N, value: V)
+    where
+        N: Into<Cow<'static, str>>,
+        V: Into<Cow<'static, str>>,
+    {
+        let name = name.into();
+        let value = value.into();
+
+        match self.attrs.iter_mut().find(|attr| attr.0 == name) {
+            Some(attr) => attr.1 = value,
+            None => self.attrs.push((name, value)),
+        }
+    }
+
+    pub fn remove_attr(&mut self, name: &str) {
+        self.attrs.retain(|attr| attr.0 != name);
+    }
+}
+
+///
+/// Passes `events` through `filter`.
+///
+pub(crate) fn apply(filter: &mut dyn Filter, events: Vec<HtmlEvent>) -> Vec<HtmlEvent> {
+    let mut output = Vec::with_capacity(events.len());
+
+    for event in events {
+        filter.filter(event, &mut output);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block_parser::BlockParser;
+    use html::{RenderError, Renderer};
+
+    ///
+    /// Wraps tables in scroll containers and removes the classes of their rows.
+    ///
+    #[derive(Debug)]
+    struct Tables;
+
+    impl Filter for Tables {
+        fn filter(&mut self, event: HtmlEvent, output: &mut Vec<HtmlEvent>) {
+            match event {
+                HtmlEvent::Start(ref tag) if tag.name == "table" => {
+                    let mut container = Tag::new("div");
+
+                    container.set_attr("class", "scroll");
+                    output.push(HtmlEvent::Start(container));
+                }
+                HtmlEvent::Start(mut tag) if tag.name == "tr" => {
+                    tag.remove_attr("class");
+
+                    return output.push(HtmlEvent::Start(tag));
+                }
+                HtmlEvent::End(ref name) if name == "table" => {
+                    output.push(event.clone());
+
+                    return output.push(HtmlEvent::End("div".into()));
+                }
+                _ => (),
+            }
+
+            output.push(event);
+        }
+    }
+
+    #[test]
+    fn filters_work() {
+        let events = vec![
+            HtmlEvent::Start(Tag {
+                name: "table".into(),
+                attrs: vec![("class".into(), "data".into())],
+            }),
+            HtmlEvent::Start(Tag {
+                name: "tr".into(),
+                attrs: vec![("class".into(), "odd".into())],
+            }),
+            HtmlEvent::Text("squid".into()),
+            HtmlEvent::End("tr".into()),
+            HtmlEvent::End("table".into()),
+        ];
+
+        assert_eq!(
+            vec![
+                HtmlEvent::Start(Tag {
+                    name: "div".into(),
+                    attrs: vec![("class".into(), "scroll".into())],
+                }),
+                HtmlEvent::Start(Tag {
+                    name: "table".into(),
+                    attrs: vec![("class".into(), "data".into())],
+                }),
+                HtmlEvent::Start(Tag::new("tr")),
+                HtmlEvent::Text("squid".into()),
+                HtmlEvent::End("tr".into()),
+                HtmlEvent::End("table".into()),
+                HtmlEvent::End("div".into()),
+            ],
+            apply(&mut Tables, events)
+        );
+    }
+
+    ///
+    /// Adds a class to sections.
+    ///
+    #[derive(Debug)]
+    struct Sections;
+
+    impl Filter for Sections {
+        fn filter(&mut self, mut event: HtmlEvent, output: &mut Vec<HtmlEvent>) {
+            if let HtmlEvent::Start(ref mut tag) = event {
+                if tag.name == "section" {
+                    tag.set_attr("class", "chapter");
+                }
+            }
+
+            output.push(event);
+        }
+    }
+
+    #[test]
+    fn sections_are_filtered() {
+        let parser = BlockParser::from_string("# Squid\n\nink\n\n# Octopus");
+        let output = Renderer::new(parser)
+            .sections(true)
+            .filter(Sections)
+            .map(|output| output.unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                "<section id=\"sec-squid\" class=\"chapter\"><h1>Squid</h1>",
+                "<p>ink</p>",
+                "</section><section id=\"sec-octopus\" class=\"chapter\"><h1>Octopus</h1>",
+                "</section>",
+            ],
+            output
+        );
+    }
+
+    #[derive(Debug)]
+    struct EndImages;
+
+    impl Filter for EndImages {
+        fn filter(&mut self, event: HtmlEvent, output: &mut Vec<HtmlEvent>) {
+            if let HtmlEvent::Start(ref tag) = event {
+                if tag.name == "img" {
+                    output.push(HtmlEvent::End("img".into()));
+                }
+            }
+
+            output.push(event);
+        }
+    }
+
+    #[test]
+    fn filtered_events_are_validated() {
+        let parser = BlockParser::from_string("![squid](squid.png)");
+        let mut renderer = Renderer::new(parser).filter(EndImages);
+
+        match renderer.next() {
+            Some(Err(RenderError::UnbalancedEndTag { .. })) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -1,5 +1,12 @@
 use super::builders::Builder;
-use super::super::ast::{HeadingLevel, Inline, ListType, Text};
+use super::context::{BlockKind, RenderContext};
+use super::highlight::Highlighter;
+use super::math;
+use super::policy::UrlPolicy;
+use super::super::constants;
+use super::super::ast::{self, Block, Decorator, FigureKind, Footnote, HeadingLevel, Inline,
+                        ListItem, ListType, NumberingStyle, PlainText, Text};
+use std::collections::HashSet;
 use std::fmt::Debug;
 
 ///
@@ -7,56 +14,311 @@ use std::fmt::Debug;
 /// A `Format` allows to customize output generation (e.g. custom tags, classes, ...)
 ///
 pub trait Format: Debug {
-    fn heading(&self, builder: &mut Builder, level: HeadingLevel, content: String) {
+    ///
+    /// Called before the first block, e.g. to render a title from the metadata.
+    /// Its elements are rendered as a separate output, so they must be closed again.
+    ///
+    fn document_start(&self, _builder: &mut Builder, _context: &mut RenderContext) {}
+
+    ///
+    /// Called after the last block and the footnotes, like `document_start`.
+    ///
+    fn document_end(&self, _builder: &mut Builder, _context: &mut RenderContext) {}
+
+    fn block(&self, builder: &mut Builder, context: &mut RenderContext, block: Block) {
+        context.enter(BlockKind::of(&block));
+
+        match block {
+            Block::Heading {
+                level,
+                content,
+                number,
+                label,
+            } => self.heading(builder, context, level, content, number, label),
+            Block::Paragraph(text) => self.paragraph(builder, context, text),
+            Block::Quote(text) => self.quote(builder, context, text),
+            Block::List(list_type, items) => self.list(builder, context, list_type, items),
+            Block::DefinitionList(entries) => self.definition_list(builder, context, entries),
+            Block::Math(source) => self.math_block(builder, context, source),
+            Block::Preformatted(Some(Decorator::Code(language)), code) => {
+                self.code_block(builder, context, language, code)
+            }
+            Block::Figure {
+                caption,
+                body,
+                number,
+                label,
+            } => self.figure(builder, context, caption, *body, number, label),
+            // Comments are never rendered
+            Block::Comment(..) => (),
+            _ => unimplemented!(),
+        }
+
+        context.leave();
+    }
+
+    ///
+    /// The id of the section started by a heading, if sections are enabled on the `Renderer`.
+    /// Defaults to a slug of the heading (e.g. `squid-ink` for "Squid Ink!"),
+    /// which is made unique by appending a number.
+    ///
+    fn section_id(&self, context: &mut RenderContext, content: &Text) -> String {
+        let id = format!("{}{}", constants::SECTION_ID_PREFIX, slug(&content.plain_text()));
+
+        unique_id(context, id)
+    }
+
+    fn heading(
+        &self,
+        builder: &mut Builder,
+        context: &mut RenderContext,
+        level: HeadingLevel,
+        content: Text,
+        number: Option<Vec<usize>>,
+        label: Option<String>,
+    ) {
         let tag = match level {
             HeadingLevel::Level1 => "h1",
             HeadingLevel::Level2 => "h2",
             HeadingLevel::Level3 => "h3",
+            HeadingLevel::Level4 => "h4",
+            HeadingLevel::Level5 => "h5",
+            HeadingLevel::Level6 => "h6",
         };
 
-        builder.tag_start(tag).finish().text(content).tag_end(tag);
+        let mut start = builder.tag_start(tag);
+
+        // Cross-references link to the label
+        if let Some(label) = label {
+            start.add_attr("id", label);
+        }
+
+        start.finish();
+
+        if let Some(number) = number {
+            self.heading_number(builder, context, &number);
+            builder.text(" ");
+        }
+
+        self.text(builder, context, content);
+
+        builder.tag_end(tag);
     }
 
-    fn paragraph(&self, builder: &mut Builder, text: Text) {
+    ///
+    /// Renders the number of a heading (e.g. `1.2`).
+    ///
+    fn heading_number(
+        &self,
+        builder: &mut Builder,
+        _context: &mut RenderContext,
+        number: &[usize],
+    ) {
+        builder
+            .tag_start("span")
+            .add_attr("class", "heading-number")
+            .finish()
+            .text(ast::format_number(number))
+            .tag_end("span");
+    }
+
+    fn paragraph(&self, builder: &mut Builder, context: &mut RenderContext, text: Text) {
         builder.tag_start("p").finish();
 
-        self.text(builder, text);
+        self.text(builder, context, text);
 
         builder.tag_end("p");
     }
 
-    fn quote(&self, builder: &mut Builder, text: Text) {
+    fn quote(&self, builder: &mut Builder, context: &mut RenderContext, text: Text) {
         builder.tag_start("blockquote").finish();
 
-        self.text(builder, text);
+        self.text(builder, context, text);
 
         builder.tag_end("blockquote");
     }
 
-    fn list(&self, builder: &mut Builder, list_type: ListType, items: Vec<Text>) {
-        let tag = match list_type {
-            ListType::Unordered => "ul",
-            ListType::Ordered => "ol",
-        };
+    fn list(
+        &self,
+        builder: &mut Builder,
+        context: &mut RenderContext,
+        list_type: ListType,
+        items: Vec<ListItem>,
+    ) {
+        match list_type {
+            ListType::Unordered => {
+                builder.tag_start("ul").finish();
 
-        builder.tag_start(tag);
+                for item in items {
+                    self.list_item(builder, context, item);
+                }
 
-        for item in items {
-            builder.tag_start("li").finish();
-            self.text(builder, item);
-            builder.tag_end("li");
+                builder.tag_end("ul");
+            }
+            ListType::Ordered { start, style } => {
+                let mut tag = builder.tag_start("ol");
+
+                if start != 1 {
+                    tag.add_attr("start", start.to_string());
+                }
+
+                if let Some(value) = numbering_style_type(style) {
+                    tag.add_attr("type", value);
+                }
+
+                tag.finish();
+
+                let mut expected = start;
+
+                for mut item in items {
+                    // Only explicit numbers that break the sequence need to be rendered
+                    if let Some(number) = item.number {
+                        if number == expected {
+                            item.number = None;
+                        }
+
+                        expected = number;
+                    }
+
+                    expected = expected.saturating_add(1);
+
+                    self.list_item(builder, context, item);
+                }
+
+                builder.tag_end("ol");
+            }
         }
+    }
 
-        builder.tag_end(tag);
+    fn list_item(&self, builder: &mut Builder, context: &mut RenderContext, item: ListItem) {
+        let mut tag = builder.tag_start("li");
+
+        if let Some(number) = item.number {
+            tag.add_attr("value", number.to_string());
+        }
+
+        tag.finish();
+
+        if let Some(checked) = item.checked {
+            self.checkbox(builder, context, checked);
+        }
+
+        self.text(builder, context, item.content);
+
+        builder.tag_end("li");
+    }
+
+    ///
+    /// Renders a block with its caption, which is prefixed with the name and number
+    /// of the figure (e.g. "Listing 2:").
+    ///
+    fn figure(
+        &self,
+        builder: &mut Builder,
+        context: &mut RenderContext,
+        caption: Text,
+        body: Block,
+        number: Option<usize>,
+        label: Option<String>,
+    ) {
+        let kind = FigureKind::of(&body);
+        let mut start = builder.tag_start("figure");
+
+        if let Some(label) = label {
+            start.add_attr("id", label);
+        }
+
+        start.finish();
+
+        // Images are not wrapped in paragraphs
+        match body {
+            Block::Paragraph(text) if kind == FigureKind::Image => {
+                self.text(builder, context, text)
+            }
+            body => self.block(builder, context, body),
+        }
+
+        builder.tag_start("figcaption").finish();
+
+        if let Some(number) = number {
+            builder
+                .tag_start("span")
+                .add_attr("class", "figure-number")
+                .finish()
+                .text(format!("{} {}:", self.figure_name(kind), number))
+                .tag_end("span")
+                .text(" ");
+        }
+
+        self.text(builder, context, caption);
+
+        builder.tag_end("figcaption").tag_end("figure");
+    }
+
+    ///
+    /// The name figures of `kind` are numbered with.
+    ///
+    fn figure_name(&self, kind: FigureKind) -> &str {
+        match kind {
+            FigureKind::Code => "Listing",
+            FigureKind::Math => "Equation",
+            _ => "Figure",
+        }
+    }
+
+    fn definition_list(
+        &self,
+        builder: &mut Builder,
+        context: &mut RenderContext,
+        entries: Vec<(Text, Vec<Block>)>,
+    ) {
+        builder.tag_start("dl").finish();
+
+        for (term, definitions) in entries {
+            builder.tag_start("dt").finish();
+            self.text(builder, context, term);
+            builder.tag_end("dt");
+
+            for definition in definitions {
+                builder.tag_start("dd").finish();
+
+                // Single line definitions are not wrapped in paragraphs, like list items
+                match definition {
+                    Block::Paragraph(text) => self.text(builder, context, text),
+                    block => self.block(builder, context, block),
+                }
+
+                builder.tag_end("dd");
+            }
+        }
+
+        builder.tag_end("dl");
+    }
+
+    ///
+    /// Renders the checkbox of a task list item.
+    /// Checkboxes are disabled by default, as toggling them
+    /// in the browser would not change the underlying document.
+    ///
+    fn checkbox(&self, builder: &mut Builder, _context: &mut RenderContext, checked: bool) {
+        let mut tag = builder.tag_start("input");
+
+        tag.add_attr("type", "checkbox").add_attr("disabled", "");
+
+        if checked {
+            tag.add_attr("checked", "");
+        }
+
+        tag.finish().text(" ");
     }
 
-    fn text(&self, builder: &mut Builder, text: Text) {
+    fn text(&self, builder: &mut Builder, context: &mut RenderContext, text: Text) {
         for inline in text {
-            self.inline(builder, inline);
+            self.inline(builder, context, inline);
         }
     }
 
-    fn inline(&self, builder: &mut Builder, inline: Inline) {
+    fn inline(&self, builder: &mut Builder, context: &mut RenderContext, inline: Inline) {
         match inline {
             Inline::LineBreak => {
                 builder.tag_start("br").finish();
@@ -64,8 +326,359 @@ pub trait Format: Debug {
             Inline::Chunk(text) => {
                 builder.text(text);
             }
+            // Targets are only missing while references are unresolved, which never happens
+            // in parsed blocks
+            Inline::Link { target, content } => {
+                self.link(builder, context, target.unwrap_or_default(), content)
+            }
+            Inline::Image { source, alt } => {
+                self.image(builder, context, source.unwrap_or_default(), alt)
+            }
+            Inline::FootnoteRef {
+                number,
+                occurrence,
+                ..
+            } => self.footnote_reference(builder, context, number, occurrence),
+            Inline::Math(source) => self.math(builder, context, source),
+            Inline::CrossRef { label, text } => {
+                let text = text.unwrap_or_else(|| label.clone());
+
+                self.cross_reference(builder, context, label, text)
+            }
+        }
+    }
+
+    ///
+    /// Renders a reference to a labeled block as a link to it.
+    ///
+    fn cross_reference(
+        &self,
+        builder: &mut Builder,
+        _context: &mut RenderContext,
+        label: String,
+        text: String,
+    ) {
+        builder
+            .tag_start("a")
+            .add_attr("href", format!("#{}", label))
+            .add_attr("class", "cross-ref")
+            .finish()
+            .text(text)
+            .tag_end("a");
+    }
+
+    ///
+    /// The policy deciding which link and image URLs are rendered.
+    /// All URLs are rendered by default.
+    ///
+    fn url_policy(&self) -> Option<&UrlPolicy> {
+        None
+    }
+
+    ///
+    /// Links with URLs rejected by the `url_policy` are rendered as their content.
+    ///
+    fn link(
+        &self,
+        builder: &mut Builder,
+        context: &mut RenderContext,
+        target: String,
+        content: Text,
+    ) {
+        let (target, rel) = match self.url_policy() {
+            None => (target, None),
+            Some(policy) => {
+                match policy.check(&target) {
+                    Some(target) => (target, policy.link_rel()),
+                    None => return self.text(builder, context, content),
+                }
+            }
+        };
+
+        let mut tag = builder.tag_start("a");
+
+        tag.add_attr("href", target);
+
+        if let Some(rel) = rel {
+            tag.add_attr("rel", rel.to_string());
+        }
+
+        tag.finish();
+
+        self.text(builder, context, content);
+
+        builder.tag_end("a");
+    }
+
+    ///
+    /// Images with URLs rejected by the `url_policy` are rendered as their alt text.
+    ///
+    fn image(
+        &self,
+        builder: &mut Builder,
+        _context: &mut RenderContext,
+        source: String,
+        alt: String,
+    ) {
+        let source = match self.url_policy() {
+            None => source,
+            Some(policy) => {
+                match policy.check(&source) {
+                    Some(source) => source,
+                    None => {
+                        builder.text(alt);
+
+                        return;
+                    }
+                }
+            }
+        };
+
+        builder
+            .tag_start("img")
+            .add_attr("src", source)
+            .add_attr("alt", alt)
+            .finish();
+    }
+
+    ///
+    /// The highlighter used for code blocks.
+    /// Defaults to the built-in highlighter if the `highlight` feature is enabled.
+    ///
+    fn highlighter(&self) -> Option<&dyn Highlighter> {
+        #[cfg(feature = "highlight")]
+        return Some(&super::highlight::DefaultHighlighter);
+
+        #[cfg(not(feature = "highlight"))]
+        None
+    }
+
+    fn code_block(
+        &self,
+        builder: &mut Builder,
+        _context: &mut RenderContext,
+        language: Option<String>,
+        code: String,
+    ) {
+        let tokens = language
+            .as_ref()
+            .and_then(|language| self.highlighter()?.highlight(language, &code));
+
+        builder.tag_start("pre").finish();
+
+        let mut tag = builder.tag_start("code");
+
+        if let Some(ref language) = language {
+            tag.add_attr("class", format!("language-{}", language));
+        }
+
+        tag.finish();
+
+        match tokens {
+            Some(tokens) => {
+                for token in tokens {
+                    match token.kind {
+                        Some(kind) => {
+                            builder
+                                .tag_start("span")
+                                .add_attr("class", kind.class_name())
+                                .finish()
+                                .text(token.text.to_string())
+                                .tag_end("span");
+                        }
+                        None => {
+                            builder.text(token.text.to_string());
+                        }
+                    }
+                }
+            }
+            None => {
+                builder.text(code);
+            }
+        }
+
+        builder.tag_end("code").tag_end("pre");
+    }
+
+    ///
+    /// Renders an inline formula as MathML.
+    /// Formulas using unsupported TeX are rendered as code.
+    ///
+    fn math(&self, builder: &mut Builder, _context: &mut RenderContext, source: String) {
+        if !math::write_mathml(builder, &source, false) {
+            builder
+                .tag_start("code")
+                .add_attr("class", "math")
+                .finish()
+                .text(source)
+                .tag_end("code");
         }
     }
+
+    fn math_block(&self, builder: &mut Builder, _context: &mut RenderContext, source: String) {
+        if !math::write_mathml(builder, &source, true) {
+            builder
+                .tag_start("pre")
+                .finish()
+                .tag_start("code")
+                .add_attr("class", "math")
+                .finish()
+                .text(source)
+                .tag_end("code")
+                .tag_end("pre");
+        }
+    }
+
+    fn footnote_reference(
+        &self,
+        builder: &mut Builder,
+        _context: &mut RenderContext,
+        number: usize,
+        occurrence: usize,
+    ) {
+        builder
+            .tag_start("sup")
+            .add_attr("id", footnote_reference_id(number, occurrence))
+            .finish();
+
+        builder
+            .tag_start("a")
+            .add_attr("href", format!("#{}", footnote_id(number)))
+            .add_attr("class", "footnote-ref")
+            .finish()
+            .text(number.to_string())
+            .tag_end("a");
+
+        builder.tag_end("sup");
+    }
+
+    ///
+    /// Renders all footnote definitions of the document, ordered by their number.
+    /// Called once after the last block.
+    ///
+    fn footnotes(
+        &self,
+        builder: &mut Builder,
+        context: &mut RenderContext,
+        footnotes: Vec<Footnote>,
+    ) {
+        builder.tag_start("section").add_attr("class", "footnotes").finish();
+        builder.tag_start("ol").finish();
+
+        let mut expected = 1;
+
+        for footnote in footnotes {
+            let mut tag = builder.tag_start("li");
+
+            tag.add_attr("id", footnote_id(footnote.number));
+
+            // Footnotes which are referenced but never defined leave gaps
+            if footnote.number != expected {
+                tag.add_attr("value", footnote.number.to_string());
+            }
+
+            tag.finish();
+
+            self.text(builder, context, footnote.content);
+
+            // Footnotes which are never referenced have nothing to link back to
+            if footnote.referenced {
+                builder
+                    .text(" ")
+                    .tag_start("a")
+                    .add_attr("href", format!("#{}", footnote_reference_id(footnote.number, 1)))
+                    .add_attr("class", "footnote-backref")
+                    .finish()
+                    .text("\u{21a9}")
+                    .tag_end("a");
+            }
+
+            builder.tag_end("li");
+
+            expected = footnote.number + 1;
+        }
+
+        builder.tag_end("ol");
+        builder.tag_end("section");
+    }
+}
+
+///
+/// The ids generated for sections. Labels never collide with generated ids,
+/// since they can't start with the same prefixes.
+///
+#[derive(Debug, Default)]
+struct Ids(HashSet<String>);
+
+///
+/// Returns `id`, or `id` followed by the first number making it unique, and marks it as used.
+///
+fn unique_id(context: &mut RenderContext, id: String) -> String {
+    let ids = &mut context.state::<Ids>().0;
+    let mut unique = id.clone();
+    let mut number = 0;
+
+    while ids.contains(&unique) {
+        number += 1;
+        unique = format!("{}-{}", id, number);
+    }
+
+    ids.insert(unique.clone());
+
+    unique
+}
+
+///
+/// Converts `text` to lower case, keeping letters and digits and replacing whitespace with dashes.
+///
+fn slug(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+
+    for word in text.split_whitespace() {
+        let word = word
+            .chars()
+            .filter(|&c| c.is_alphanumeric() || c == '-' || c == '_')
+            .flat_map(char::to_lowercase)
+            .collect::<String>();
+
+        if word.is_empty() {
+            continue;
+        }
+
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+
+        slug.push_str(&word);
+    }
+
+    if slug.is_empty() {
+        slug.push_str("section");
+    }
+
+    slug
+}
+
+fn footnote_id(number: usize) -> String {
+    format!("{}{}", constants::FOOTNOTE_ID_PREFIX, number)
+}
+
+fn footnote_reference_id(number: usize, occurrence: usize) -> String {
+    if occurrence == 1 {
+        format!("{}{}", constants::FOOTNOTE_REFERENCE_ID_PREFIX, number)
+    } else {
+        format!("{}{}-{}", constants::FOOTNOTE_REFERENCE_ID_PREFIX, number, occurrence)
+    }
+}
+
+fn numbering_style_type(style: NumberingStyle) -> Option<&'static str> {
+    match style {
+        NumberingStyle::Decimal => None,
+        NumberingStyle::LowerAlpha => Some("a"),
+        NumberingStyle::UpperAlpha => Some("A"),
+        NumberingStyle::LowerRoman => Some("i"),
+        NumberingStyle::UpperRoman => Some("I"),
+    }
 }
 
 #[derive(Debug)]
@@ -73,25 +686,423 @@ pub struct DefaultFormat;
 
 impl Format for DefaultFormat {}
 
+///
+/// A format for rendering untrusted input.
+/// Only `http`, `https` and `mailto` URLs are rendered
+/// and links are marked with `rel="nofollow noopener"`.
+///
+/// # Example
+///
+/// ```
+/// use squid::BlockParser;
+/// use squid::html::{Renderer, SafeFormat};
+///
+/// let parser = BlockParser::from_string("[click me](javascript:steal)");
+/// let mut renderer = Renderer::with_format(SafeFormat::new(), parser);
+///
+/// assert_eq!("<p>click me</p>", renderer.next().unwrap().unwrap().to_string());
+/// ```
+///
+#[derive(Debug)]
+pub struct SafeFormat {
+    policy: UrlPolicy,
+}
+
+impl SafeFormat {
+    pub fn new() -> Self {
+        SafeFormat::with_policy(UrlPolicy::new().rel("nofollow noopener"))
+    }
+
+    pub fn with_policy(policy: UrlPolicy) -> Self {
+        SafeFormat { policy }
+    }
+}
+
+impl Default for SafeFormat {
+    fn default() -> Self {
+        SafeFormat::new()
+    }
+}
+
+impl Format for SafeFormat {
+    fn url_policy(&self) -> Option<&UrlPolicy> {
+        Some(&self.policy)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use ast::HeadingLevel;
+    use block_parser::BlockParser;
+    use html::Renderer;
+    use html::highlight::{Token, TokenKind};
 
     #[test]
     fn default_heading_works() {
         let format = DefaultFormat;
         let mut builder = Builder::new();
+        let mut context = RenderContext::new();
 
-        format.heading(&mut builder, HeadingLevel::Level1, "hello world".into());
+        format.heading(
+            &mut builder,
+            &mut context,
+            HeadingLevel::Level1,
+            vec![Inline::Chunk("hello world".into())],
+            None,
+            None,
+        );
 
-        format.heading(&mut builder, HeadingLevel::Level2, "level 2".into());
+        format.heading(
+            &mut builder,
+            &mut context,
+            HeadingLevel::Level2,
+            vec![Inline::Chunk("level 2".into())],
+            None,
+            None,
+        );
 
-        format.heading(&mut builder, HeadingLevel::Level3, "level 3".into());
+        format.heading(
+            &mut builder,
+            &mut context,
+            HeadingLevel::Level3,
+            vec![Inline::Chunk("level 3".into())],
+            Some(vec![1, 1, 2]),
+            Some("deep".into()),
+        );
+
+        format.heading(
+            &mut builder,
+            &mut context,
+            HeadingLevel::Level6,
+            vec![Inline::Chunk("level 6".into())],
+            None,
+            None,
+        );
+
+        assert_eq!(
+            "<h1>hello world</h1><h2>level 2</h2>\
+             <h3 id=\"deep\"><span class=\"heading-number\">1.1.2</span> level 3</h3>\
+             <h6>level 6</h6>",
+            format!("{}", builder.consume().unwrap())
+        );
+    }
+
+    #[test]
+    fn default_list_works() {
+        let format = DefaultFormat;
+        let mut builder = Builder::new();
+        let mut context = RenderContext::new();
+
+        format.list(
+            &mut builder,
+            &mut context,
+            ListType::Unordered,
+            vec![
+                ListItem {
+                    checked: None,
+                    checkbox_span: None,
+                    number: None,
+                    content: vec![Inline::Chunk("foo".into())],
+                },
+                ListItem {
+                    checked: Some(false),
+                    checkbox_span: None,
+                    number: None,
+                    content: vec![Inline::Chunk("do".into())],
+                },
+                ListItem {
+                    checked: Some(true),
+                    checkbox_span: None,
+                    number: None,
+                    content: vec![Inline::Chunk("this".into())],
+                },
+            ],
+        );
+
+        assert_eq!(
+            "<ul><li>foo</li>\
+             <li><input type=\"checkbox\" disabled=\"\"> do</li>\
+             <li><input type=\"checkbox\" disabled=\"\" checked=\"\"> this</li></ul>",
+            format!("{}", builder.consume().unwrap())
+        );
+    }
+
+    #[test]
+    fn default_ordered_list_works() {
+        let format = DefaultFormat;
+        let mut builder = Builder::new();
+        let mut context = RenderContext::new();
+
+        format.list(
+            &mut builder,
+            &mut context,
+            ListType::Ordered {
+                start: 3,
+                style: NumberingStyle::LowerAlpha,
+            },
+            vec![
+                ListItem {
+                    checked: None,
+                    checkbox_span: None,
+                    number: Some(3),
+                    content: vec![Inline::Chunk("foo".into())],
+                },
+                ListItem {
+                    checked: None,
+                    checkbox_span: None,
+                    number: None,
+                    content: vec![Inline::Chunk("bar".into())],
+                },
+                ListItem {
+                    checked: None,
+                    checkbox_span: None,
+                    number: Some(7),
+                    content: vec![Inline::Chunk("baz".into())],
+                },
+                ListItem {
+                    checked: None,
+                    checkbox_span: None,
+                    number: Some(8),
+                    content: vec![Inline::Chunk("qux".into())],
+                },
+            ],
+        );
+
+        assert_eq!(
+            "<ol start=\"3\" type=\"a\"><li>foo</li><li>bar</li>\
+             <li value=\"7\">baz</li><li>qux</li></ol>",
+            format!("{}", builder.consume().unwrap())
+        );
+    }
+
+    #[test]
+    fn default_definition_list_works() {
+        let format = DefaultFormat;
+        let mut builder = Builder::new();
+        let mut context = RenderContext::new();
+
+        format.definition_list(
+            &mut builder,
+            &mut context,
+            vec![
+                (
+                    vec![Inline::Chunk("Squid".into())],
+                    vec![
+                        Block::Paragraph(vec![Inline::Chunk("a cephalopod".into())]),
+                        Block::Paragraph(vec![Inline::Chunk("a parser".into())]),
+                    ],
+                ),
+                (vec![Inline::Chunk("Ink".into())], vec![]),
+            ],
+        );
+
+        assert_eq!(
+            "<dl><dt>Squid</dt><dd>a cephalopod</dd><dd>a parser</dd><dt>Ink</dt></dl>",
+            format!("{}", builder.consume().unwrap())
+        );
+    }
+
+    #[test]
+    fn default_figure_works() {
+        let format = DefaultFormat;
+        let mut builder = Builder::new();
+        let mut context = RenderContext::new();
+
+        format.figure(
+            &mut builder,
+            &mut context,
+            vec![Inline::Chunk("Squid".into())],
+            Block::Paragraph(vec![
+                Inline::Image {
+                    source: Some("squid.png".into()),
+                    alt: "squid".into(),
+                },
+            ]),
+            Some(2),
+            Some("squid".into()),
+        );
+
+        format.figure(
+            &mut builder,
+            &mut context,
+            vec![Inline::Chunk("Ink".into())],
+            Block::Preformatted(Some(Decorator::Code(None)), "ink".into()),
+            None,
+            None,
+        );
+
+        assert_eq!(
+            "<figure id=\"squid\"><img src=\"squid.png\" alt=\"squid\">\
+             <figcaption><span class=\"figure-number\">Figure 2:</span> Squid</figcaption>\
+             </figure><figure><pre><code>ink</code></pre><figcaption>Ink</figcaption></figure>",
+            format!("{}", builder.consume().unwrap())
+        );
+    }
+
+    #[test]
+    fn default_math_works() {
+        let format = DefaultFormat;
+        let mut builder = Builder::new();
+        let mut context = RenderContext::new();
+
+        format.math_block(&mut builder, &mut context, "\\frac{a}{b}".into());
+        format.math_block(&mut builder, &mut context, "\\unknown <x>".into());
+
+        assert_eq!(
+            "<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\">\
+             <mfrac><mi>a</mi><mi>b</mi></mfrac></math>\
+             <pre><code class=\"math\">\\unknown &lt;x&gt;</code></pre>",
+            format!("{}", builder.consume().unwrap())
+        );
+    }
+
+    #[test]
+    fn code_block_works() {
+        #[derive(Debug)]
+        struct NumberHighlighter;
+
+        impl Highlighter for NumberHighlighter {
+            fn highlight<'a>(&self, _: &str, code: &'a str) -> Option<Vec<Token<'a>>> {
+                let (text, number) = code.split_at(code.len() - 1);
+
+                Some(vec![
+                    Token { kind: None, text },
+                    Token { kind: Some(TokenKind::Number), text: number },
+                ])
+            }
+        }
+
+        #[derive(Debug)]
+        struct HighlightFormat;
+
+        impl Format for HighlightFormat {
+            fn highlighter(&self) -> Option<&dyn Highlighter> {
+                Some(&NumberHighlighter)
+            }
+        }
+
+        let mut builder = Builder::new();
+        let mut context = RenderContext::new();
+
+        HighlightFormat.code_block(
+            &mut builder,
+            &mut context,
+            Some("squid".into()),
+            "a < 1".into(),
+        );
+        HighlightFormat.code_block(&mut builder, &mut context, None, "a < 1".into());
+
+        assert_eq!(
+            "<pre><code class=\"language-squid\">a &lt; <span class=\"tok-number\">1</span>\
+             </code></pre><pre><code>a &lt; 1</code></pre>",
+            format!("{}", builder.consume().unwrap())
+        );
+    }
+
+    #[test]
+    fn safe_format_prevents_scripts() {
+        let input = "[a](javascript:alert(1)) [b](JAVASCRIPT:alert(1)) ![c](data:text/html,x)\n\
+                     [d](vbscript:x) [e](x\"onclick=\"alert(1)) <script>alert(1)</script>\n\
+                     [f][f] ![g][g] $\\text{<script>}$ https://squid.rs/\"><script>\n\n\
+                     [f]: javascript:alert(1)\n[g]: jAvAsCrIpT:alert(1)\n\n\
+                     [code \"><script>alert(1)</script>]\n---\n<script>alert(1)</script>\n---";
+        let parser = BlockParser::from_string(input).autolink(true);
+        let output = Renderer::with_format(SafeFormat::new(), parser)
+            .map(|output| output.unwrap().to_string())
+            .collect::<String>();
+
+        assert!(!output.to_lowercase().contains("javascript:"), "{}", output);
+        assert!(!output.contains("vbscript:"), "{}", output);
+        assert!(!output.contains("data:"), "{}", output);
+        assert!(!output.contains("<script"), "{}", output);
+        assert!(!output.contains("\"onclick"), "{}", output);
+        assert!(output.contains(
+            "<a href=\"https://squid.rs/&quot;&gt;&lt;script\" rel=\"nofollow noopener\">"
+        ));
+
+        let parser = BlockParser::from_string("![x](javascript:alert(1))");
+        let output = Renderer::with_format(SafeFormat::new(), parser)
+            .map(|output| output.unwrap().to_string())
+            .collect::<String>();
+
+        assert_eq!("<p>x</p>", output);
+    }
+
+    #[test]
+    fn default_link_and_image_works() {
+        let format = DefaultFormat;
+        let mut builder = Builder::new();
+        let mut context = RenderContext::new();
+
+        format.paragraph(
+            &mut builder,
+            &mut context,
+            vec![
+                Inline::Link {
+                    target: Some("/squid?a=1&b=2".into()),
+                    content: vec![
+                        Inline::Image {
+                            source: Some("squid.png".into()),
+                            alt: "\"squid\"".into(),
+                        },
+                    ],
+                },
+            ],
+        );
+
+        assert_eq!(
+            "<p><a href=\"/squid?a=1&amp;b=2\">\
+             <img src=\"squid.png\" alt=\"&quot;squid&quot;\"></a></p>",
+            format!("{}", builder.consume().unwrap())
+        );
+    }
+
+    #[test]
+    fn default_footnotes_works() {
+        let format = DefaultFormat;
+        let mut builder = Builder::new();
+        let mut context = RenderContext::new();
+
+        format.paragraph(
+            &mut builder,
+            &mut context,
+            vec![
+                Inline::FootnoteRef {
+                    label: "ink".into(),
+                    number: 1,
+                    occurrence: 2,
+                },
+            ],
+        );
+
+        format.footnotes(
+            &mut builder,
+            &mut context,
+            vec![
+                Footnote {
+                    number: 1,
+                    label: "ink".into(),
+                    content: vec![Inline::Chunk("sepia".into())],
+                    referenced: true,
+                },
+                Footnote {
+                    number: 3,
+                    label: "arms".into(),
+                    content: vec![Inline::Chunk("eight".into())],
+                    referenced: false,
+                },
+            ],
+        );
 
         assert_eq!(
-            "<h1>hello world</h1><h2>level 2</h2><h3>level 3</h3>",
-            format!("{}", builder.consume())
+            "<p><sup id=\"fnref-1-2\"><a href=\"#fn-1\" class=\"footnote-ref\">1</a></sup></p>\
+             <section class=\"footnotes\"><ol>\
+             <li id=\"fn-1\">sepia \
+             <a href=\"#fnref-1\" class=\"footnote-backref\">\u{21a9}</a></li>\
+             <li id=\"fn-3\" value=\"3\">eight</li>\
+             </ol></section>",
+            format!("{}", builder.consume().unwrap())
         );
     }
 }
@@ -0,0 +1,587 @@
+use super::{Highlighter, Token, TokenKind};
+
+///
+/// Describes the lexical structure of a language,
+/// which is all the (heuristic) highlighter knows about it.
+///
+#[derive(Debug)]
+struct Language {
+    names: &'static [&'static str],
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    literals: &'static [&'static str],
+    /// Whether keywords, types and literals are matched case insensitively (SQL)
+    ignore_case: bool,
+    /// Whether capitalized identifiers are types
+    capitalized_types: bool,
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// Whether strings can be delimited by three quotes (e.g. `"""`)
+    triple_quotes: bool,
+    /// Characters allowed inside of identifiers, besides alphanumerics and `_`
+    identifier_chars: &'static str,
+    /// Strings and identifiers followed by this character are keys
+    key_separator: Option<char>,
+    /// Whether `$name` is a variable
+    variables: bool,
+    /// Whether `name!(…)` is a macro call
+    macros: bool,
+    /// Whether `[…]` at the start of a line is a key (TOML tables)
+    table_headers: bool,
+}
+
+const RUST: Language = Language {
+    names: &["rust", "rs"],
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "self", "static", "struct", "super", "trait", "type", "unsafe",
+        "use", "where", "while",
+    ],
+    types: &[
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+        "f32", "f64", "bool", "char", "str",
+    ],
+    literals: &["true", "false"],
+    ignore_case: false,
+    capitalized_types: true,
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    triple_quotes: false,
+    identifier_chars: "",
+    key_separator: None,
+    variables: false,
+    macros: true,
+    table_headers: false,
+};
+
+const JAVASCRIPT_KEYWORDS: &[&str] = &[
+    "async", "await", "break", "case", "catch", "class", "const", "continue", "debugger",
+    "default", "delete", "do", "else", "export", "extends", "finally", "for", "from", "function",
+    "if", "import", "in", "instanceof", "let", "new", "of", "return", "static", "super", "switch",
+    "this", "throw", "try", "typeof", "var", "void", "while", "with", "yield",
+];
+
+const JAVASCRIPT_LITERALS: &[&str] = &["true", "false", "null", "undefined", "NaN", "Infinity"];
+
+const JAVASCRIPT: Language = Language {
+    names: &["javascript", "js", "jsx"],
+    keywords: JAVASCRIPT_KEYWORDS,
+    types: &[],
+    literals: JAVASCRIPT_LITERALS,
+    ignore_case: false,
+    capitalized_types: true,
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+    triple_quotes: false,
+    identifier_chars: "$",
+    key_separator: None,
+    variables: false,
+    macros: false,
+    table_headers: false,
+};
+
+const TYPESCRIPT: Language = Language {
+    names: &["typescript", "ts", "tsx"],
+    keywords: &[
+        "abstract", "as", "async", "await", "break", "case", "catch", "class", "const",
+        "continue", "declare", "default", "delete", "do", "else", "enum", "export", "extends",
+        "finally", "for", "from", "function", "if", "implements", "import", "in", "instanceof",
+        "interface", "is", "keyof", "let", "namespace", "new", "of", "private", "protected",
+        "public", "readonly", "return", "static", "super", "switch", "this", "throw", "try",
+        "type", "typeof", "var", "void", "while", "yield",
+    ],
+    types: &[
+        "any", "bigint", "boolean", "never", "number", "object", "string", "symbol", "unknown",
+    ],
+    ..JAVASCRIPT
+};
+
+const PYTHON: Language = Language {
+    names: &["python", "py"],
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+        "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with",
+        "yield",
+    ],
+    types: &[
+        "bool", "bytes", "dict", "float", "frozenset", "int", "list", "object", "set", "str",
+        "tuple",
+    ],
+    literals: &["True", "False", "None"],
+    ignore_case: false,
+    capitalized_types: true,
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    triple_quotes: true,
+    identifier_chars: "",
+    key_separator: None,
+    variables: false,
+    macros: false,
+    table_headers: false,
+};
+
+const SHELL: Language = Language {
+    names: &["shell", "sh", "bash", "zsh"],
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+        "in", "local", "readonly", "return", "select", "then", "until", "while",
+    ],
+    types: &[],
+    literals: &["true", "false"],
+    ignore_case: false,
+    capitalized_types: false,
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    triple_quotes: false,
+    identifier_chars: "-",
+    key_separator: None,
+    variables: true,
+    macros: false,
+    table_headers: false,
+};
+
+const JSON: Language = Language {
+    names: &["json"],
+    keywords: &[],
+    types: &[],
+    literals: &["true", "false", "null"],
+    ignore_case: false,
+    capitalized_types: false,
+    line_comments: &[],
+    block_comment: None,
+    quotes: &['"'],
+    triple_quotes: false,
+    identifier_chars: "",
+    key_separator: Some(':'),
+    variables: false,
+    macros: false,
+    table_headers: false,
+};
+
+const TOML: Language = Language {
+    names: &["toml"],
+    literals: &["true", "false", "inf", "nan"],
+    line_comments: &["#"],
+    quotes: &['"', '\''],
+    triple_quotes: true,
+    identifier_chars: "-",
+    key_separator: Some('='),
+    table_headers: true,
+    ..JSON
+};
+
+const SQL: Language = Language {
+    names: &["sql"],
+    keywords: &[
+        "add", "all", "alter", "and", "as", "asc", "begin", "between", "by", "case", "check",
+        "commit", "constraint", "create", "cross", "default", "delete", "desc", "distinct", "drop",
+        "else", "end", "exists", "foreign", "from", "full", "group", "having", "if", "in", "index",
+        "inner", "insert", "into", "is", "join", "key", "left", "like", "limit", "not", "offset",
+        "on", "or", "order", "outer", "primary", "references", "returning", "right", "rollback",
+        "select", "set", "table", "then", "transaction", "union", "unique", "update", "using",
+        "values", "view", "when", "where", "with",
+    ],
+    types: &[
+        "bigint", "boolean", "char", "date", "decimal", "float", "int", "integer", "numeric",
+        "real", "serial", "smallint", "text", "time", "timestamp", "varchar",
+    ],
+    literals: &["true", "false", "null"],
+    ignore_case: true,
+    capitalized_types: false,
+    line_comments: &["--"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['\''],
+    triple_quotes: false,
+    identifier_chars: "",
+    key_separator: None,
+    variables: false,
+    macros: false,
+    table_headers: false,
+};
+
+const LANGUAGES: &[Language] = &[RUST, JAVASCRIPT, TYPESCRIPT, PYTHON, SHELL, JSON, TOML, SQL];
+
+///
+/// A lightweight highlighter for Rust, JavaScript, TypeScript, Python,
+/// shell scripts, JSON, TOML and SQL.
+///
+#[derive(Debug, Default)]
+pub struct DefaultHighlighter;
+
+impl Highlighter for DefaultHighlighter {
+    fn highlight<'a>(&self, language: &str, code: &'a str) -> Option<Vec<Token<'a>>> {
+        let language = language.to_lowercase();
+        let language = LANGUAGES
+            .iter()
+            .find(|candidate| candidate.names.contains(&language.as_str()))?;
+
+        Some(Lexer::new(language, code).tokenize())
+    }
+}
+
+struct Lexer<'a> {
+    language: &'static Language,
+    code: &'a str,
+    position: usize,
+    tokens: Vec<Token<'a>>,
+    /// Start of the text that is not highlighted and not yet added to `tokens`
+    plain_start: usize,
+}
+
+fn contains(words: &[&str], word: &str, ignore_case: bool) -> bool {
+    if ignore_case {
+        words.iter().any(|candidate| candidate.eq_ignore_ascii_case(word))
+    } else {
+        words.contains(&word)
+    }
+}
+
+impl<'a> Lexer<'a> {
+    fn new(language: &'static Language, code: &'a str) -> Self {
+        Lexer {
+            language,
+            code,
+            position: 0,
+            tokens: Vec::new(),
+            plain_start: 0,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.code[self.position..]
+    }
+
+    fn is_identifier_char(&self, c: char) -> bool {
+        c.is_alphanumeric() || c == '_' || self.language.identifier_chars.contains(c)
+    }
+
+    fn is_line_start(&self) -> bool {
+        self.code[..self.position]
+            .chars()
+            .rev()
+            .take_while(|&c| c != '\n')
+            .all(char::is_whitespace)
+    }
+
+    fn next_non_whitespace(&self, offset: usize) -> Option<char> {
+        self.rest()[offset..].chars().find(|c| !c.is_whitespace())
+    }
+
+    fn push(&mut self, kind: TokenKind, length: usize) {
+        if self.plain_start < self.position {
+            self.tokens.push(Token {
+                kind: None,
+                text: &self.code[self.plain_start..self.position],
+            });
+        }
+
+        self.tokens.push(Token {
+            kind: Some(kind),
+            text: &self.code[self.position..self.position + length],
+        });
+
+        self.position += length;
+        self.plain_start = self.position;
+    }
+
+    fn tokenize(mut self) -> Vec<Token<'a>> {
+        while let Some(c) = self.rest().chars().next() {
+            match self.scan(c) {
+                (Some(kind), length) => self.push(kind, length),
+                (None, length) => self.position += length,
+            }
+        }
+
+        if self.plain_start < self.code.len() {
+            self.tokens.push(Token {
+                kind: None,
+                text: &self.code[self.plain_start..],
+            });
+        }
+
+        self.tokens
+    }
+
+    ///
+    /// Returns the kind (`None` if it's not highlighted) and length of the token starting with `c`.
+    ///
+    fn scan(&self, c: char) -> (Option<TokenKind>, usize) {
+        let rest = self.rest();
+        let language = self.language;
+
+        if language.line_comments.iter().any(|&comment| rest.starts_with(comment)) {
+            return (Some(TokenKind::Comment), rest.find('\n').unwrap_or(rest.len()));
+        }
+
+        if let Some((start, end)) = language.block_comment {
+            if let Some(comment) = rest.strip_prefix(start) {
+                let length = comment
+                    .find(end)
+                    .map_or(rest.len(), |index| start.len() + index + end.len());
+
+                return (Some(TokenKind::Comment), length);
+            }
+        }
+
+        if language.quotes.contains(&c) {
+            let length = self.scan_string(c);
+            let kind = if self.is_key(length) {
+                TokenKind::Key
+            } else {
+                TokenKind::String
+            };
+
+            return (Some(kind), length);
+        }
+
+        if language.table_headers && c == '[' && self.is_line_start() {
+            return (Some(TokenKind::Key), rest.find('\n').unwrap_or(rest.len()));
+        }
+
+        if language.variables && c == '$' {
+            let length = self.scan_variable();
+
+            return if length > 1 {
+                (Some(TokenKind::Variable), length)
+            } else {
+                (None, length)
+            };
+        }
+
+        if c.is_ascii_digit() {
+            return (Some(TokenKind::Number), self.scan_number());
+        }
+
+        if self.is_identifier_char(c) {
+            let length = rest.find(|c| !self.is_identifier_char(c)).unwrap_or(rest.len());
+
+            return (self.classify(&rest[..length], length), length);
+        }
+
+        (None, c.len_utf8())
+    }
+
+    fn scan_string(&self, quote: char) -> usize {
+        let rest = self.rest();
+        let triple = quote.to_string().repeat(3);
+
+        if self.language.triple_quotes && rest.starts_with(&triple) {
+            return rest[3..].find(&triple).map_or(rest.len(), |index| index + 6);
+        }
+
+        let mut chars = rest.char_indices().skip(1);
+
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                // Unterminated strings end at the end of the line (except for template strings)
+                '\n' if quote != '`' => return index,
+                c if c == quote => return index + c.len_utf8(),
+                _ => {}
+            }
+        }
+
+        rest.len()
+    }
+
+    fn scan_variable(&self) -> usize {
+        let rest = self.rest();
+
+        if rest[1..].starts_with('{') {
+            return rest.find('}').map_or(1, |index| index + 1);
+        }
+
+        let length = rest[1..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len() - 1);
+
+        // Special variables (e.g. `$?` or `$@`)
+        if length == 0 && rest[1..].starts_with(|c| "?@#*!$-".contains(c)) {
+            return 2;
+        }
+
+        1 + length
+    }
+
+    fn scan_number(&self) -> usize {
+        let rest = self.rest();
+        let mut chars = rest.char_indices().peekable();
+
+        while let Some((index, c)) = chars.next() {
+            let is_decimal_point = c == '.' &&
+                chars.peek().is_some_and(|&(_, next)| next.is_ascii_digit());
+
+            if !(c.is_ascii_alphanumeric() || c == '_' || is_decimal_point) {
+                return index;
+            }
+        }
+
+        rest.len()
+    }
+
+    ///
+    /// Whether the token of the given length at the current position
+    /// is followed by a key separator.
+    ///
+    fn is_key(&self, length: usize) -> bool {
+        self.language.key_separator.is_some() &&
+            self.next_non_whitespace(length) == self.language.key_separator
+    }
+
+    fn classify(&self, word: &str, length: usize) -> Option<TokenKind> {
+        let language = self.language;
+        let next = self.rest()[length..].chars().next();
+
+        if contains(language.keywords, word, language.ignore_case) {
+            return Some(TokenKind::Keyword);
+        }
+
+        if contains(language.literals, word, language.ignore_case) {
+            return Some(TokenKind::Literal);
+        }
+
+        if contains(language.types, word, language.ignore_case) ||
+            language.capitalized_types && word.starts_with(char::is_uppercase)
+        {
+            return Some(TokenKind::Type);
+        }
+
+        if next == Some('(') || language.macros && next == Some('!') {
+            return Some(TokenKind::Function);
+        }
+
+        if self.is_key(length) {
+            Some(TokenKind::Key)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlight(language: &str, code: &str) -> Vec<(Option<TokenKind>, String)> {
+        DefaultHighlighter
+            .highlight(language, code)
+            .unwrap()
+            .into_iter()
+            .map(|token| (token.kind, token.text.to_string()))
+            .collect()
+    }
+
+    fn token(kind: Option<TokenKind>, text: &str) -> (Option<TokenKind>, String) {
+        (kind, text.into())
+    }
+
+    #[test]
+    fn rust_works() {
+        assert_eq!(
+            vec![
+                token(Some(TokenKind::Keyword), "fn"),
+                token(None, " "),
+                token(Some(TokenKind::Function), "main"),
+                token(None, "() { "),
+                token(Some(TokenKind::Keyword), "let"),
+                token(None, " x2: "),
+                token(Some(TokenKind::Type), "Vec"),
+                token(None, "<"),
+                token(Some(TokenKind::Type), "u8"),
+                token(None, "> = "),
+                token(Some(TokenKind::Function), "vec"),
+                token(None, "!["),
+                token(Some(TokenKind::Number), "0x1f"),
+                token(None, ", "),
+                token(Some(TokenKind::Number), "1.5"),
+                token(None, "]; "),
+                token(Some(TokenKind::String), "\"a \\\" b\""),
+                token(None, " "),
+                token(Some(TokenKind::Comment), "// done"),
+                token(None, "\n}"),
+            ],
+            highlight(
+                "Rust",
+                "fn main() { let x2: Vec<u8> = vec![0x1f, 1.5]; \"a \\\" b\" // done\n}",
+            )
+        );
+    }
+
+    #[test]
+    fn keys_work() {
+        assert_eq!(
+            vec![
+                token(None, "{"),
+                token(Some(TokenKind::Key), "\"a\""),
+                token(None, ": "),
+                token(Some(TokenKind::String), "\"b\""),
+                token(None, ", "),
+                token(Some(TokenKind::Key), "\"c\""),
+                token(None, ": ["),
+                token(Some(TokenKind::Literal), "null"),
+                token(None, "]}"),
+            ],
+            highlight("json", "{\"a\": \"b\", \"c\": [null]}")
+        );
+
+        assert_eq!(
+            vec![
+                token(Some(TokenKind::Key), "[package]"),
+                token(None, "\n"),
+                token(Some(TokenKind::Key), "name-x"),
+                token(None, " = "),
+                token(Some(TokenKind::String), "'squid'"),
+                token(None, " "),
+                token(Some(TokenKind::Comment), "# name"),
+            ],
+            highlight("toml", "[package]\nname-x = 'squid' # name")
+        );
+    }
+
+    #[test]
+    fn sql_and_shell_work() {
+        assert_eq!(
+            vec![
+                token(Some(TokenKind::Keyword), "SELECT"),
+                token(None, " name "),
+                token(Some(TokenKind::Keyword), "FROM"),
+                token(None, " squids "),
+                token(Some(TokenKind::Keyword), "WHERE"),
+                token(None, " arms = "),
+                token(Some(TokenKind::Number), "8"),
+                token(None, " "),
+                token(Some(TokenKind::Comment), "-- eight"),
+            ],
+            highlight("sql", "SELECT name FROM squids WHERE arms = 8 -- eight")
+        );
+
+        assert_eq!(
+            vec![
+                token(Some(TokenKind::Keyword), "if"),
+                token(None, " [ "),
+                token(Some(TokenKind::Variable), "$?"),
+                token(None, " ]; "),
+                token(Some(TokenKind::Keyword), "then"),
+                token(None, " echo "),
+                token(Some(TokenKind::String), "\"${HOME}\""),
+                token(None, " $ "),
+                token(Some(TokenKind::Variable), "$USER"),
+            ],
+            highlight("sh", "if [ $? ]; then echo \"${HOME}\" $ $USER")
+        );
+    }
+
+    #[test]
+    fn unknown_languages_are_not_highlighted() {
+        assert_eq!(None, DefaultHighlighter.highlight("brainfuck", "+[-]"));
+    }
+}
@@ -0,0 +1,127 @@
+//!
+//! Syntax highlighting for code blocks (`[code rust]`).
+//!
+//! Highlighted code is split into tokens, which are rendered as `<span class="tok-…">`.
+//! The built-in highlighter requires the `highlight` feature.
+//! Other highlighters can be plugged in by implementing `Highlighter`
+//! and returning it from `Format::highlighter`.
+//!
+
+#[cfg(feature = "highlight")]
+mod languages;
+
+#[cfg(feature = "highlight")]
+pub use self::languages::DefaultHighlighter;
+
+use std::fmt::{Debug, Write};
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[non_exhaustive]
+pub enum TokenKind {
+    Keyword,
+    /// Built-in and (by convention) user defined types
+    Type,
+    /// e.g. `true`, `false` or `null`
+    Literal,
+    String,
+    Number,
+    Comment,
+    /// Called functions and macros
+    Function,
+    /// Shell variables (e.g. `$HOME`)
+    Variable,
+    /// Keys of JSON objects and TOML tables
+    Key,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Token<'a> {
+    /// `None` for text that is not highlighted
+    pub kind: Option<TokenKind>,
+    pub text: &'a str,
+}
+
+pub trait Highlighter: Debug {
+    ///
+    /// Splits `code` into tokens. The tokens must cover the code completely.
+    /// Returns `None` if the language is not supported.
+    ///
+    fn highlight<'a>(&self, language: &str, code: &'a str) -> Option<Vec<Token<'a>>>;
+}
+
+///
+/// The colors of the light theme, as used by `theme_css`.
+///
+pub const LIGHT_THEME: &[(TokenKind, &str)] = &[
+    (TokenKind::Keyword, "#d73a49"),
+    (TokenKind::Type, "#6f42c1"),
+    (TokenKind::Literal, "#005cc5"),
+    (TokenKind::String, "#032f62"),
+    (TokenKind::Number, "#005cc5"),
+    (TokenKind::Comment, "#6a737d"),
+    (TokenKind::Function, "#6f42c1"),
+    (TokenKind::Variable, "#e36209"),
+    (TokenKind::Key, "#22863a"),
+];
+
+///
+/// The colors of the dark theme, as used by `theme_css`.
+///
+pub const DARK_THEME: &[(TokenKind, &str)] = &[
+    (TokenKind::Keyword, "#ff7b72"),
+    (TokenKind::Type, "#d2a8ff"),
+    (TokenKind::Literal, "#79c0ff"),
+    (TokenKind::String, "#a5d6ff"),
+    (TokenKind::Number, "#79c0ff"),
+    (TokenKind::Comment, "#8b949e"),
+    (TokenKind::Function, "#d2a8ff"),
+    (TokenKind::Variable, "#ffa657"),
+    (TokenKind::Key, "#7ee787"),
+];
+
+impl TokenKind {
+    ///
+    /// The class of the `<span>` the token is rendered as.
+    ///
+    pub fn class_name(self) -> &'static str {
+        match self {
+            TokenKind::Keyword => "tok-keyword",
+            TokenKind::Type => "tok-type",
+            TokenKind::Literal => "tok-literal",
+            TokenKind::String => "tok-string",
+            TokenKind::Number => "tok-number",
+            TokenKind::Comment => "tok-comment",
+            TokenKind::Function => "tok-function",
+            TokenKind::Variable => "tok-variable",
+            TokenKind::Key => "tok-key",
+        }
+    }
+}
+
+///
+/// Generates a stylesheet coloring the highlighted tokens.
+///
+/// # Example
+///
+/// ```
+/// use squid::html::highlight::{theme_css, LIGHT_THEME};
+///
+/// let css = theme_css(LIGHT_THEME);
+///
+/// assert!(css.contains(".tok-keyword { color: #d73a49; }"));
+/// ```
+///
+pub fn theme_css(colors: &[(TokenKind, &str)]) -> String {
+    let mut css = String::new();
+
+    for &(kind, color) in colors {
+        // Writing to a string can't fail
+        writeln!(css, ".{} {{ color: {}; }}", kind.class_name(), color).unwrap();
+
+        if kind == TokenKind::Comment {
+            writeln!(css, ".{} {{ font-style: italic; }}", kind.class_name()).unwrap();
+        }
+    }
+
+    css
+}
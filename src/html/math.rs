@@ -0,0 +1,595 @@
+use super::builders::Builder;
+use std::iter::Peekable;
+use std::str::Chars;
+
+const MATHML_NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+
+///
+/// A node of the MathML tree generated from TeX.
+///
+#[derive(Debug, PartialEq)]
+enum Node {
+    /// Contains the identifier and whether it is set upright
+    Identifier(String, bool),
+    Number(String),
+    Operator(String),
+    Text(String),
+    /// Contains the width
+    Space(&'static str),
+    Row(Vec<Node>),
+    Fraction(Box<Node>, Box<Node>),
+    /// Contains the radicand and the (optional) index
+    Root(Box<Node>, Option<Box<Node>>),
+    Scripts {
+        base: Box<Node>,
+        sub: Option<Box<Node>>,
+        sup: Option<Box<Node>>,
+    },
+}
+
+const GREEK_LETTERS: &[(&str, &str)] = &[
+    ("alpha", "α"),
+    ("beta", "β"),
+    ("gamma", "γ"),
+    ("delta", "δ"),
+    ("epsilon", "ϵ"),
+    ("varepsilon", "ε"),
+    ("zeta", "ζ"),
+    ("eta", "η"),
+    ("theta", "θ"),
+    ("vartheta", "ϑ"),
+    ("iota", "ι"),
+    ("kappa", "κ"),
+    ("lambda", "λ"),
+    ("mu", "μ"),
+    ("nu", "ν"),
+    ("xi", "ξ"),
+    ("pi", "π"),
+    ("rho", "ρ"),
+    ("sigma", "σ"),
+    ("tau", "τ"),
+    ("upsilon", "υ"),
+    ("phi", "ϕ"),
+    ("varphi", "φ"),
+    ("chi", "χ"),
+    ("psi", "ψ"),
+    ("omega", "ω"),
+];
+
+const UPPERCASE_GREEK_LETTERS: &[(&str, &str)] = &[
+    ("Gamma", "Γ"),
+    ("Delta", "Δ"),
+    ("Theta", "Θ"),
+    ("Lambda", "Λ"),
+    ("Xi", "Ξ"),
+    ("Pi", "Π"),
+    ("Sigma", "Σ"),
+    ("Upsilon", "Υ"),
+    ("Phi", "Φ"),
+    ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+];
+
+const OPERATORS: &[(&str, &str)] = &[
+    ("cdot", "⋅"),
+    ("times", "×"),
+    ("div", "÷"),
+    ("pm", "±"),
+    ("mp", "∓"),
+    ("ast", "∗"),
+    ("circ", "∘"),
+    ("le", "≤"),
+    ("leq", "≤"),
+    ("ge", "≥"),
+    ("geq", "≥"),
+    ("ne", "≠"),
+    ("neq", "≠"),
+    ("ll", "≪"),
+    ("gg", "≫"),
+    ("approx", "≈"),
+    ("equiv", "≡"),
+    ("sim", "∼"),
+    ("simeq", "≃"),
+    ("propto", "∝"),
+    ("in", "∈"),
+    ("notin", "∉"),
+    ("ni", "∋"),
+    ("subset", "⊂"),
+    ("subseteq", "⊆"),
+    ("supset", "⊃"),
+    ("supseteq", "⊇"),
+    ("cup", "∪"),
+    ("cap", "∩"),
+    ("setminus", "∖"),
+    ("emptyset", "∅"),
+    ("land", "∧"),
+    ("wedge", "∧"),
+    ("lor", "∨"),
+    ("vee", "∨"),
+    ("neg", "¬"),
+    ("to", "→"),
+    ("rightarrow", "→"),
+    ("leftarrow", "←"),
+    ("leftrightarrow", "↔"),
+    ("Rightarrow", "⇒"),
+    ("Leftarrow", "⇐"),
+    ("Leftrightarrow", "⇔"),
+    ("implies", "⟹"),
+    ("iff", "⟺"),
+    ("mapsto", "↦"),
+    ("forall", "∀"),
+    ("exists", "∃"),
+    ("infty", "∞"),
+    ("partial", "∂"),
+    ("nabla", "∇"),
+    ("sum", "∑"),
+    ("prod", "∏"),
+    ("int", "∫"),
+    ("oint", "∮"),
+    ("ldots", "…"),
+    ("cdots", "⋯"),
+    ("vdots", "⋮"),
+    ("ddots", "⋱"),
+    ("langle", "⟨"),
+    ("rangle", "⟩"),
+    ("lfloor", "⌊"),
+    ("rfloor", "⌋"),
+    ("lceil", "⌈"),
+    ("rceil", "⌉"),
+    ("mid", "∣"),
+];
+
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh",
+    "tanh", "log", "ln", "lg", "exp", "lim", "max", "min", "sup", "inf", "det", "gcd", "deg",
+    "dim", "ker",
+];
+
+const SPACES: &[(&str, &str)] = &[("quad", "1em"), ("qquad", "2em")];
+
+fn lookup(table: &[(&str, &'static str)], name: &str) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|&&(key, _)| key == name)
+        .map(|&(_, value)| value)
+}
+
+fn operator(c: char) -> Option<&'static str> {
+    let value = match c {
+        '+' => "+",
+        '-' => "\u{2212}",
+        '*' => "∗",
+        '=' => "=",
+        '<' => "<",
+        '>' => ">",
+        '(' => "(",
+        ')' => ")",
+        '[' => "[",
+        ']' => "]",
+        '|' => "|",
+        ',' => ",",
+        ';' => ";",
+        ':' => ":",
+        '!' => "!",
+        '/' => "/",
+        '.' => ".",
+        '\'' => "′",
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+///
+/// The maximum nesting depth of groups and commands, which keeps deeply nested input
+/// from overflowing the stack.
+///
+const MAX_DEPTH: usize = 256;
+
+///
+/// Parses the supported subset of TeX. Returns `None` for anything that is not supported.
+///
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Parser {
+            chars: input.chars().peekable(),
+            depth: 0,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    ///
+    /// Parses nodes until `terminator` (which is consumed) or the end of the input,
+    /// if there is no terminator.
+    ///
+    fn parse_row(&mut self, terminator: Option<char>) -> Option<Vec<Node>> {
+        let mut nodes = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+
+            match self.chars.peek().cloned() {
+                None if terminator.is_none() => return Some(nodes),
+                None => return None,
+                Some(c) if Some(c) == terminator => {
+                    self.chars.next();
+
+                    return Some(nodes);
+                }
+                Some('}') => return None,
+                Some(c @ '^') | Some(c @ '_') => {
+                    self.chars.next();
+
+                    let script = self.parse_argument()?;
+                    let base = nodes.pop().unwrap_or_else(|| Node::Row(Vec::new()));
+
+                    nodes.push(attach_script(base, c == '^', script)?);
+                }
+                Some(_) => {
+                    let node = self.parse_atom(false)?;
+
+                    nodes.push(node);
+                }
+            }
+        }
+    }
+
+    ///
+    /// Parses the argument of a command or script, which is either a group or a single atom.
+    ///
+    fn parse_argument(&mut self) -> Option<Node> {
+        self.skip_whitespace();
+
+        self.parse_atom(true)
+    }
+
+    ///
+    /// Parses an atom, failing if atoms are nested more than `MAX_DEPTH` levels deep.
+    ///
+    fn parse_atom(&mut self, single: bool) -> Option<Node> {
+        if self.depth >= MAX_DEPTH {
+            return None;
+        }
+
+        self.depth += 1;
+
+        let node = self.parse_nested_atom(single);
+
+        self.depth -= 1;
+
+        node
+    }
+
+    fn parse_nested_atom(&mut self, single: bool) -> Option<Node> {
+        let c = self.chars.next()?;
+
+        match c {
+            '{' => self.parse_row(Some('}')).map(into_node),
+            '\\' => self.parse_command(),
+            c if c.is_ascii_digit() && single => Some(Node::Number(c.to_string())),
+            c if c.is_ascii_digit() => {
+                let mut number = c.to_string();
+
+                while let Some(&next) = self.chars.peek() {
+                    let is_decimal_point = next == '.' &&
+                        self.chars.clone().nth(1).is_some_and(|c| c.is_ascii_digit());
+
+                    if !next.is_ascii_digit() && !is_decimal_point {
+                        break;
+                    }
+
+                    number.push(next);
+                    self.chars.next();
+                }
+
+                Some(Node::Number(number))
+            }
+            c if c.is_alphabetic() => Some(Node::Identifier(c.to_string(), false)),
+            c => operator(c).map(|value| Node::Operator(value.into())),
+        }
+    }
+
+    fn parse_command(&mut self) -> Option<Node> {
+        let name = self.parse_command_name()?;
+
+        match name.as_str() {
+            "," => Some(Node::Space("0.167em")),
+            ":" | ">" => Some(Node::Space("0.222em")),
+            ";" => Some(Node::Space("0.278em")),
+            "{" | "}" | "|" | "%" | "$" | "#" | "&" => Some(Node::Operator(name)),
+            "frac" => {
+                let numerator = self.parse_argument()?;
+                let denominator = self.parse_argument()?;
+
+                Some(Node::Fraction(Box::new(numerator), Box::new(denominator)))
+            }
+            "sqrt" => {
+                self.skip_whitespace();
+
+                let index = if self.chars.peek() == Some(&'[') {
+                    self.chars.next();
+
+                    Some(Box::new(into_node(self.parse_row(Some(']'))?)))
+                } else {
+                    None
+                };
+
+                Some(Node::Root(Box::new(self.parse_argument()?), index))
+            }
+            "text" | "mathrm" => {
+                let text = self.parse_raw_group()?;
+
+                if name == "text" {
+                    Some(Node::Text(text))
+                } else {
+                    Some(Node::Identifier(text, true))
+                }
+            }
+            "left" | "right" => {
+                self.skip_whitespace();
+
+                match self.chars.peek() {
+                    // `\left.` is an invisible delimiter
+                    Some(&'.') => {
+                        self.chars.next();
+
+                        Some(Node::Row(Vec::new()))
+                    }
+                    _ => {
+                        match self.parse_atom(true)? {
+                            node @ Node::Operator(..) => Some(node),
+                            _ => None,
+                        }
+                    }
+                }
+            }
+            name => {
+                if let Some(letter) = lookup(GREEK_LETTERS, name) {
+                    Some(Node::Identifier(letter.into(), false))
+                } else if let Some(letter) = lookup(UPPERCASE_GREEK_LETTERS, name) {
+                    Some(Node::Identifier(letter.into(), true))
+                } else if let Some(value) = lookup(OPERATORS, name) {
+                    Some(Node::Operator(value.into()))
+                } else if let Some(width) = lookup(SPACES, name) {
+                    Some(Node::Space(width))
+                } else if FUNCTIONS.contains(&name) {
+                    Some(Node::Identifier(name.into(), true))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    ///
+    /// Commands are either a sequence of letters (e.g. `\alpha`) or a single other character.
+    ///
+    fn parse_command_name(&mut self) -> Option<String> {
+        let first = self.chars.next()?;
+
+        if !first.is_ascii_alphabetic() {
+            return Some(first.to_string());
+        }
+
+        let mut name = first.to_string();
+
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_ascii_alphabetic() {
+                break;
+            }
+
+            name.push(c);
+            self.chars.next();
+        }
+
+        Some(name)
+    }
+
+    ///
+    /// Returns the unparsed content of a group (e.g. the argument of `\text`).
+    ///
+    fn parse_raw_group(&mut self) -> Option<String> {
+        self.skip_whitespace();
+
+        if self.chars.next()? != '{' {
+            return None;
+        }
+
+        let mut value = String::new();
+
+        loop {
+            match self.chars.next()? {
+                '}' => return Some(value),
+                '{' => return None,
+                c => value.push(c),
+            }
+        }
+    }
+}
+
+fn into_node(mut nodes: Vec<Node>) -> Node {
+    if nodes.len() == 1 {
+        nodes.remove(0)
+    } else {
+        Node::Row(nodes)
+    }
+}
+
+fn attach_script(base: Node, superscript: bool, script: Node) -> Option<Node> {
+    let script = Some(Box::new(script));
+
+    match base {
+        Node::Scripts { base, sub, sup } => {
+            // A second subscript (or superscript) on the same base is an error in TeX
+            match (superscript, sub.is_some(), sup.is_some()) {
+                (true, _, false) => Some(Node::Scripts { base, sub, sup: script }),
+                (false, false, _) => Some(Node::Scripts { base, sub: script, sup }),
+                _ => None,
+            }
+        }
+        base if superscript => {
+            Some(Node::Scripts {
+                base: Box::new(base),
+                sub: None,
+                sup: script,
+            })
+        }
+        base => {
+            Some(Node::Scripts {
+                base: Box::new(base),
+                sub: script,
+                sup: None,
+            })
+        }
+    }
+}
+
+fn parse(source: &str) -> Option<Vec<Node>> {
+    Parser::new(source).parse_row(None)
+}
+
+fn write_element(builder: &mut Builder, name: &'static str, children: Vec<Node>) {
+    builder.tag_start(name).finish();
+
+    for child in children {
+        write_node(builder, child);
+    }
+
+    builder.tag_end(name);
+}
+
+fn write_token(builder: &mut Builder, name: &'static str, value: String) {
+    builder.tag_start(name).finish().text(value).tag_end(name);
+}
+
+fn write_node(builder: &mut Builder, node: Node) {
+    match node {
+        Node::Identifier(value, true) if value.chars().count() == 1 => {
+            builder
+                .tag_start("mi")
+                .add_attr("mathvariant", "normal")
+                .finish()
+                .text(value)
+                .tag_end("mi");
+        }
+        Node::Identifier(value, _) => write_token(builder, "mi", value),
+        Node::Number(value) => write_token(builder, "mn", value),
+        Node::Operator(value) => write_token(builder, "mo", value),
+        Node::Text(value) => write_token(builder, "mtext", value),
+        Node::Space(width) => {
+            builder
+                .tag_start("mspace")
+                .add_attr("width", width)
+                .finish()
+                .tag_end("mspace");
+        }
+        Node::Row(children) => write_element(builder, "mrow", children),
+        Node::Fraction(numerator, denominator) => {
+            write_element(builder, "mfrac", vec![*numerator, *denominator])
+        }
+        Node::Root(radicand, None) => write_element(builder, "msqrt", vec![*radicand]),
+        Node::Root(radicand, Some(index)) => {
+            write_element(builder, "mroot", vec![*radicand, *index])
+        }
+        Node::Scripts { base, sub, sup } => {
+            match (sub, sup) {
+                (Some(sub), Some(sup)) => {
+                    write_element(builder, "msubsup", vec![*base, *sub, *sup])
+                }
+                (Some(sub), None) => write_element(builder, "msub", vec![*base, *sub]),
+                (None, Some(sup)) => write_element(builder, "msup", vec![*base, *sup]),
+                (None, None) => write_node(builder, *base),
+            }
+        }
+    }
+}
+
+///
+/// Converts TeX to MathML. Nothing is written and `false` is returned
+/// if the source uses unsupported commands or is malformed.
+///
+pub fn write_mathml(builder: &mut Builder, source: &str, display: bool) -> bool {
+    let nodes = match parse(source) {
+        Some(nodes) => nodes,
+        None => return false,
+    };
+
+    let mut tag = builder.tag_start("math");
+
+    tag.add_attr("xmlns", MATHML_NAMESPACE);
+
+    if display {
+        tag.add_attr("display", "block");
+    }
+
+    tag.finish();
+
+    for node in nodes {
+        write_node(builder, node);
+    }
+
+    builder.tag_end("math");
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mathml(source: &str) -> Option<String> {
+        let mut builder = Builder::new();
+
+        if write_mathml(&mut builder, source, false) {
+            Some(format!("{}", builder.consume().unwrap()))
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn mathml_works() {
+        assert_eq!(
+            Some(
+                "<math xmlns=\"http://www.w3.org/1998/Math/MathML\">\
+                 <msup><mi>x</mi><mn>2</mn></msup><mo>+</mo>\
+                 <mfrac><mn>1.5</mn><msqrt><mi>α</mi></msqrt></mfrac><mo>≤</mo>\
+                 <msubsup><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>0</mn></mrow>\
+                 <mi>n</mi></msubsup>\
+                 <mi>sin</mi><mi mathvariant=\"normal\">Ω</mi><mtext>if ok</mtext></math>"
+                    .into()
+            ),
+            mathml(
+                "x^2 + \\frac{1.5}{\\sqrt{\\alpha}} \\leq \\sum_{i=0}^n \
+                 \\sin \\Omega \\text{if ok}",
+            )
+        );
+    }
+
+    #[test]
+    fn unsupported_tex_is_rejected() {
+        assert_eq!(None, mathml("\\unknown{x}"));
+        assert_eq!(None, mathml("x_1_2"));
+        assert_eq!(None, mathml("\\frac{1}{2"));
+        assert_eq!(None, mathml("a}"));
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        let nested = |depth| format!("{}x{}", "{".repeat(depth), "}".repeat(depth));
+
+        assert!(mathml(&nested(100)).is_some());
+        assert_eq!(None, mathml(&nested(10_000)));
+        assert_eq!(None, mathml(&"\\sqrt{".repeat(10_000)));
+    }
+}
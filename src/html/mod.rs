@@ -1,9 +1,17 @@
+mod context;
 mod format;
 mod renderer;
 mod escape;
+mod filter;
+mod math;
 mod output;
+mod policy;
 pub mod builders;
+pub mod highlight;
 
 pub use self::output::*;
+pub use self::context::*;
 pub use self::format::*;
+pub use self::filter::*;
+pub use self::policy::*;
 pub use self::renderer::*;
@@ -1,23 +1,390 @@
-use super::builders::Event;
+use super::builders::{write_escaped, Builder, Event};
+use super::filter::{HtmlEvent, Tag};
+use super::renderer::RenderError;
 use std::fmt;
 
+///
+/// Elements that are put on their own lines when pretty-printing.
+///
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "dd", "details", "div", "dl", "dt",
+    "figcaption", "figure", "footer", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li",
+    "main", "nav", "ol", "p", "pre", "section", "table", "tbody", "td", "tfoot", "th", "thead",
+    "tr", "ul",
+];
+
+///
+/// Elements whose whitespace is significant and therefore never changed.
+///
+const RAW_ELEMENTS: &[&str] = &["pre", "textarea", "script", "style"];
+
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+#[non_exhaustive]
+pub enum OutputMode {
+    /// HTML5 syntax, e.g. `<br>`
+    #[default]
+    Html,
+    /// Well-formed XML, e.g. `<br />`, for XHTML and EPUB
+    Xhtml,
+}
+
+///
+/// How whitespace between elements is written.
+///
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+#[non_exhaustive]
+pub enum Formatting {
+    /// Elements are written as they are built, without added whitespace
+    #[default]
+    Compact,
+    /// Block elements are put on their own lines, indented by `indent` spaces per level.
+    /// Outputs end with a line break, so the blocks of a document are on their own lines, too.
+    Pretty { indent: usize },
+    /// Runs of whitespace are collapsed and whitespace around block elements is removed
+    Minified,
+}
+
 #[derive(Debug)]
 pub struct Output {
     events: Vec<Event>,
+    mode: OutputMode,
+    formatting: Formatting,
+}
+
+fn is_block_element(name: &str) -> bool {
+    BLOCK_ELEMENTS.contains(&name)
+}
+
+fn is_raw_element(name: &str) -> bool {
+    RAW_ELEMENTS.contains(&name)
+}
+
+///
+/// Whether `event` starts or ends a block element.
+///
+fn is_block_boundary(event: Option<&Event>) -> bool {
+    match event {
+        Some(&Event::TagStart { ref name, .. }) |
+        Some(&Event::VoidTag { ref name, .. }) |
+        Some(&Event::TagEnd { ref name }) => is_block_element(name),
+        _ => false,
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut previous_is_whitespace = false;
+
+    for c in text.chars() {
+        let is_whitespace = c.is_whitespace();
+
+        if !is_whitespace {
+            collapsed.push(c);
+        } else if !previous_is_whitespace {
+            collapsed.push(' ');
+        }
+
+        previous_is_whitespace = is_whitespace;
+    }
+
+    collapsed
 }
 
 impl Output {
     pub(crate) fn new(events: Vec<Event>) -> Self {
-        Output { events }
+        Output {
+            events,
+            mode: OutputMode::default(),
+            formatting: Formatting::default(),
+        }
+    }
+
+    ///
+    /// Builds the output from `events`, failing if they contain invalid names
+    /// or unbalanced end tags. Elements that are still open at the end are closed.
+    ///
+    pub fn from_events<E>(events: E) -> Result<Self, RenderError>
+    where
+        E: IntoIterator<Item = HtmlEvent>,
+    {
+        Output::from_unbalanced_events(events, 0, 0)
+    }
+
+    ///
+    /// Builds the output from `events` like `from_events`, except that the first `ends`
+    /// end tags without a started element end elements started in earlier outputs,
+    /// and the outermost `starts` elements that are still open at the end stay open.
+    ///
+    pub(crate) fn from_unbalanced_events<E>(
+        events: E,
+        mut ends: usize,
+        starts: usize,
+    ) -> Result<Self, RenderError>
+    where
+        E: IntoIterator<Item = HtmlEvent>,
+    {
+        let mut builder = Builder::new();
+
+        for event in events {
+            match event {
+                HtmlEvent::Start(tag) => {
+                    let mut start = builder.tag_start(tag.name);
+
+                    for (name, value) in tag.attrs {
+                        start.add_attr(name, value);
+                    }
+
+                    start.finish();
+                }
+                HtmlEvent::Text(text) => {
+                    builder.text(text);
+                }
+                HtmlEvent::End(name) => {
+                    if ends > 0 && builder.is_balanced() {
+                        ends -= 1;
+                        builder.unbalanced_end(name);
+                    } else {
+                        builder.tag_end(name);
+                    }
+                }
+            }
+        }
+
+        builder.consume_keeping_open(starts)
+    }
+
+    ///
+    /// Returns the number of end tags of elements started in earlier outputs
+    /// and the number of elements that are ended in later outputs.
+    ///
+    pub(crate) fn unbalanced(&self) -> (usize, usize) {
+        let mut ends = 0;
+        let mut open = 0;
+
+        for event in &self.events {
+            match *event {
+                Event::TagStart { .. } => open += 1,
+                Event::TagEnd { .. } if open == 0 => ends += 1,
+                Event::TagEnd { .. } => open -= 1,
+                _ => (),
+            }
+        }
+
+        (ends, open)
+    }
+
+    ///
+    /// Returns the events of the output, e.g. to transform them.
+    ///
+    pub fn into_events(self) -> Vec<HtmlEvent> {
+        self.events
+            .into_iter()
+            .map(|event| match event {
+                Event::TagStart { name, attrs } | Event::VoidTag { name, attrs } => {
+                    HtmlEvent::Start(Tag { name, attrs })
+                }
+                Event::Text { text } => HtmlEvent::Text(text),
+                Event::TagEnd { name } => HtmlEvent::End(name),
+            })
+            .collect()
+    }
+
+    ///
+    /// Sets the syntax the output is written in.
+    ///
+    pub fn with_mode(mut self, mode: OutputMode) -> Self {
+        self.mode = mode;
+
+        self
+    }
+
+    ///
+    /// Inserts the events of `output` before the events of this output.
+    ///
+    pub(crate) fn prepend(mut self, mut output: Output) -> Self {
+        output.events.append(&mut self.events);
+        self.events = output.events;
+
+        self
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn mode(&self) -> OutputMode {
+        self.mode
+    }
+
+    ///
+    /// Sets how whitespace between elements is written.
+    ///
+    pub fn with_formatting(mut self, formatting: Formatting) -> Self {
+        self.formatting = formatting;
+
+        self
+    }
+
+    pub fn formatting(&self) -> Formatting {
+        self.formatting
+    }
+
+    fn write_pretty(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        // Whether the open block elements contain other block elements, innermost last
+        let mut blocks: Vec<bool> = Vec::new();
+        // The number of open elements whose content is written unchanged
+        let mut raw = 0;
+        let mut is_first = true;
+
+        for event in &self.events {
+            let is_line_start = match *event {
+                Event::TagStart { ref name, .. } | Event::VoidTag { ref name, .. }
+                    if raw == 0 && is_block_element(name) =>
+                {
+                    if let Some(has_blocks) = blocks.last_mut() {
+                        *has_blocks = true;
+                    }
+
+                    !is_first
+                }
+                Event::TagEnd { ref name } => {
+                    if is_raw_element(name) {
+                        raw -= 1;
+                    }
+
+                    // Only block elements containing other block elements end on their own line
+                    raw == 0 && is_block_element(name) && blocks.pop() == Some(true)
+                }
+                _ => false,
+            };
+
+            if is_line_start {
+                write!(f, "\n{:width$}", "", width = blocks.len() * indent)?;
+            }
+
+            if let Event::TagStart { ref name, .. } = *event {
+                if raw == 0 && is_block_element(name) {
+                    blocks.push(false);
+                }
+
+                if is_raw_element(name) {
+                    raw += 1;
+                }
+            }
+
+            event.write(f, self.mode)?;
+            is_first = false;
+        }
+
+        if is_first {
+            Ok(())
+        } else {
+            writeln!(f)
+        }
+    }
+
+    fn write_minified(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut raw = 0;
+
+        for (index, event) in self.events.iter().enumerate() {
+            match *event {
+                Event::Text { ref text } if raw == 0 => {
+                    let mut text = &collapse_whitespace(text)[..];
+
+                    if index == 0 || is_block_boundary(self.events.get(index - 1)) {
+                        text = text.trim_start();
+                    }
+
+                    if is_block_boundary(self.events.get(index + 1)) {
+                        text = text.trim_end();
+                    }
+
+                    write_escaped(f, text, self.mode)?;
+                    continue;
+                }
+                Event::TagStart { ref name, .. } if is_raw_element(name) => raw += 1,
+                Event::TagEnd { ref name } if is_raw_element(name) => raw -= 1,
+                _ => (),
+            }
+
+            event.write(f, self.mode)?;
+        }
+
+        Ok(())
     }
 }
 
 impl fmt::Display for Output {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for event in &self.events {
-            write!(f, "{}", event)?;
+        match self.formatting {
+            Formatting::Compact => {
+                for event in &self.events {
+                    event.write(f, self.mode)?;
+                }
+
+                Ok(())
+            }
+            Formatting::Pretty { indent } => self.write_pretty(f, indent),
+            Formatting::Minified => self.write_minified(f),
         }
+    }
+}
 
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::builders::Builder;
+
+    fn build() -> Output {
+        let mut builder = Builder::new();
+
+        builder
+            .tag_start("ul")
+            .finish()
+            .tag_start("li")
+            .finish()
+            .text("  squid   ink ")
+            .tag_start("em")
+            .finish()
+            .text("!")
+            .tag_end("em")
+            .tag_end("li")
+            .tag_start("li")
+            .finish()
+            .tag_start("pre")
+            .finish()
+            .tag_start("code")
+            .finish()
+            .text("fn main() {\n    ink();\n}")
+            .tag_end("code")
+            .tag_end("pre")
+            .tag_start("hr")
+            .finish()
+            .tag_end("li")
+            .tag_end("ul");
+
+        builder.consume().unwrap()
+    }
+
+    #[test]
+    fn pretty_formatting_works() {
+        let output = build().with_formatting(Formatting::Pretty { indent: 2 });
+
+        assert_eq!(
+            "<ul>\n  <li>  squid   ink <em>!</em></li>\n  <li>\n    \
+             <pre><code>fn main() {\n    ink();\n}</code></pre>\n    <hr>\n  </li>\n</ul>\n",
+            output.to_string()
+        );
+    }
+
+    #[test]
+    fn minified_formatting_works() {
+        let output = build().with_formatting(Formatting::Minified);
+
+        assert_eq!(
+            "<ul><li>squid ink <em>!</em></li><li>\
+             <pre><code>fn main() {\n    ink();\n}</code></pre><hr></li></ul>",
+            output.to_string()
+        );
     }
 }
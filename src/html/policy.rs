@@ -0,0 +1,203 @@
+use std::borrow::Cow;
+
+const SCHEME_SEPARATOR: char = ':';
+const AUTHORITY_PREFIX: &str = "//";
+
+///
+/// Decides which link and image URLs are rendered, e.g. to keep `javascript:` URLs
+/// from untrusted input out of the output. URLs without a scheme are relative and always allowed.
+///
+/// # Example
+///
+/// ```
+/// use squid::html::UrlPolicy;
+///
+/// let policy = UrlPolicy::new()
+///     .base("https://squid.rs/docs/")
+///     .rel("nofollow noopener");
+///
+/// assert_eq!(Some("https://example.com".into()), policy.check("https://example.com"));
+/// assert_eq!(Some("https://squid.rs/docs/intro".into()), policy.check("intro"));
+/// assert_eq!(Some("https://squid.rs/logo.png".into()), policy.check("/logo.png"));
+/// assert_eq!(None, policy.check("javascript:alert(1)"));
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct UrlPolicy {
+    schemes: Vec<Cow<'static, str>>,
+    base: Option<String>,
+    rel: Option<String>,
+}
+
+impl Default for UrlPolicy {
+    fn default() -> Self {
+        UrlPolicy {
+            schemes: vec!["http".into(), "https".into(), "mailto".into()],
+            base: None,
+            rel: None,
+        }
+    }
+}
+
+///
+/// Returns the scheme of `url`, if it is absolute.
+///
+fn scheme(url: &str) -> Option<&str> {
+    let end = url.find(SCHEME_SEPARATOR)?;
+    let scheme = &url[..end];
+    let mut chars = scheme.chars();
+
+    let is_valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic()) &&
+        chars.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+
+    if is_valid {
+        Some(scheme)
+    } else {
+        None
+    }
+}
+
+///
+/// Returns the scheme and host of `url` (e.g. `https://squid.rs`).
+///
+fn origin(url: &str) -> &str {
+    let authority_start = url.find(AUTHORITY_PREFIX).map_or(0, |index| index + 2);
+    let end = url[authority_start..]
+        .find('/')
+        .map_or(url.len(), |index| authority_start + index);
+
+    &url[..end]
+}
+
+impl UrlPolicy {
+    ///
+    /// Creates a policy allowing `http`, `https` and `mailto` URLs.
+    ///
+    pub fn new() -> Self {
+        UrlPolicy::default()
+    }
+
+    pub fn allow_scheme<S>(mut self, scheme: S) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        self.schemes.push(scheme.into());
+
+        self
+    }
+
+    ///
+    /// Resolves relative URLs against `base`.
+    ///
+    pub fn base<S>(mut self, base: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.base = Some(base.into());
+
+        self
+    }
+
+    ///
+    /// Sets the `rel` attribute of links (e.g. `nofollow noopener`).
+    ///
+    pub fn rel<S>(mut self, rel: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.rel = Some(rel.into());
+
+        self
+    }
+
+    pub fn link_rel(&self) -> Option<&str> {
+        self.rel.as_deref()
+    }
+
+    ///
+    /// Returns the URL to render, or `None` if the URL is not allowed.
+    ///
+    pub fn check(&self, url: &str) -> Option<String> {
+        // Browsers ignore these characters, e.g. `java\tscript:` is a `javascript:` URL
+        let url = url
+            .trim_matches(|c: char| c <= ' ')
+            .chars()
+            .filter(|&c| c != '\t' && c != '\n' && c != '\r')
+            .collect::<String>();
+
+        match scheme(&url) {
+            Some(scheme) => {
+                let is_allowed = self
+                    .schemes
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(scheme));
+
+                if is_allowed {
+                    Some(url)
+                } else {
+                    None
+                }
+            }
+            None => Some(self.resolve(url)),
+        }
+    }
+
+    fn resolve(&self, url: String) -> String {
+        let base = match self.base {
+            Some(ref base) => base,
+            None => return url,
+        };
+
+        if url.starts_with('#') || url.starts_with(AUTHORITY_PREFIX) {
+            url
+        } else if url.starts_with('/') {
+            format!("{}{}", origin(base), url)
+        } else if base.ends_with('/') {
+            format!("{}{}", base, url)
+        } else {
+            format!("{}/{}", base, url)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsafe_schemes_are_rejected() {
+        let policy = UrlPolicy::new();
+
+        for url in &[
+            "javascript:alert(1)",
+            "JaVaScRiPt:alert(1)",
+            " javascript:alert(1)",
+            "java\tscript:alert(1)",
+            "\u{1}javascript:alert(1)",
+            "vbscript:msgbox(1)",
+            "data:text/html,<script>alert(1)</script>",
+        ] {
+            assert_eq!(None, policy.check(url), "{:?}", url);
+        }
+    }
+
+    #[test]
+    fn allowed_urls_are_kept() {
+        let policy = UrlPolicy::new().allow_scheme("ftp");
+
+        assert_eq!(Some("HTTPS://squid.rs".into()), policy.check("HTTPS://squid.rs"));
+        assert_eq!(Some("ftp://squid.rs".into()), policy.check("ftp://squid.rs"));
+        assert_eq!(Some("docs/a:b".into()), policy.check("docs/a:b"));
+        assert_eq!(Some("#top".into()), policy.check("#top"));
+    }
+
+    #[test]
+    fn relative_urls_are_resolved() {
+        let policy = UrlPolicy::new().base("https://squid.rs/docs");
+
+        assert_eq!(Some("https://squid.rs/docs/intro".into()), policy.check("intro"));
+        assert_eq!(Some("https://squid.rs/logo.png".into()), policy.check("/logo.png"));
+        assert_eq!(Some("//cdn.squid.rs/a.js".into()), policy.check("//cdn.squid.rs/a.js"));
+        assert_eq!(Some("#top".into()), policy.check("#top"));
+    }
+}
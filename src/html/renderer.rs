@@ -2,13 +2,25 @@ use std::fmt;
 use std::error::Error;
 use super::format::{Format, DefaultFormat};
 use super::builders::Builder;
-use super::output::Output;
+use super::context::RenderContext;
+use super::filter::{self, Filter};
+use super::output::{Formatting, Output, OutputMode};
 use super::super::error::ParseError;
-use super::super::ast::Block;
+use super::super::ast::{Block, Footnote, HeadingLevel, Text};
+use std::mem;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum RenderError {
     ParseError(ParseError),
+    /// A tag or attribute name that is not valid HTML. Contains the name.
+    InvalidName(String),
+    /// An end tag that doesn't match the innermost open element
+    /// (`None` if there is no open element).
+    UnbalancedEndTag {
+        expected: Option<String>,
+        found: String,
+    },
 }
 
 ///
@@ -16,10 +28,15 @@ pub enum RenderError {
 ///
 /// ```
 /// use squid::html::Renderer;
-/// use squid::ast::{Block, HeadingLevel};
+/// use squid::ast::{Block, HeadingLevel, Inline};
 ///
 /// let blocks = vec![
-///     Ok(Block::Heading(HeadingLevel::Level1, "Hello World".into())),
+///     Ok(Block::Heading {
+///         level: HeadingLevel::Level1,
+///         content: vec![Inline::Chunk("Hello World".into())],
+///         number: None,
+///         label: None,
+///     }),
 /// ];
 ///
 /// let mut renderer = Renderer::new(blocks.into_iter());
@@ -43,6 +60,20 @@ where
     // Not using Cow because Cow would require F to be `Clone`able
     format: F,
     input: I,
+    /// Footnote definitions are collected and rendered after the last block
+    footnotes: Vec<Footnote>,
+    mode: OutputMode,
+    formatting: Formatting,
+    filters: Vec<Box<dyn Filter>>,
+    context: RenderContext,
+    /// Whether `Format::document_start` has been called
+    started: bool,
+    /// Whether `Format::document_end` has been called
+    ended: bool,
+    /// Whether headings and their content are wrapped in sections
+    sections: bool,
+    /// The levels of the headings starting the open sections, outermost first
+    open_sections: Vec<HeadingLevel>,
 }
 
 impl<I> Renderer<DefaultFormat, I>
@@ -56,13 +87,35 @@ where
         Renderer {
             input,
             format: DefaultFormat,
+            footnotes: Vec::new(),
+            mode: OutputMode::default(),
+            formatting: Formatting::default(),
+            filters: Vec::new(),
+            context: RenderContext::new(),
+            started: false,
+            ended: false,
+            sections: false,
+            open_sections: Vec::new(),
         }
     }
 }
 
 impl fmt::Display for RenderError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.description())
+        match *self {
+            RenderError::ParseError(ref err) => write!(f, "{}", err),
+            RenderError::InvalidName(ref name) => {
+                write!(f, "`{}` is not a valid tag or attribute name", name)
+            }
+            RenderError::UnbalancedEndTag {
+                expected: Some(ref expected),
+                ref found,
+            } => write!(f, "expected end tag `{}`, found `{}`", expected, found),
+            RenderError::UnbalancedEndTag {
+                expected: None,
+                ref found,
+            } => write!(f, "end tag `{}` has no matching start tag", found),
+        }
     }
 }
 
@@ -70,12 +123,15 @@ impl Error for RenderError {
     fn description(&self) -> &str {
         match *self {
             RenderError::ParseError(ref err) => err.description(),
+            RenderError::InvalidName(..) => "invalid tag or attribute name",
+            RenderError::UnbalancedEndTag { .. } => "unbalanced end tag",
         }
     }
 
     fn cause(&self) -> Option<&Error> {
         match *self {
             RenderError::ParseError(ref err) => Some(err),
+            _ => None,
         }
     }
 }
@@ -92,7 +148,216 @@ where
     I: Iterator<Item = Result<Block, ParseError>>,
 {
     pub fn with_format(format: F, input: I) -> Self {
-        Renderer { format, input }
+        Renderer {
+            format,
+            input,
+            footnotes: Vec::new(),
+            mode: OutputMode::default(),
+            formatting: Formatting::default(),
+            filters: Vec::new(),
+            context: RenderContext::new(),
+            started: false,
+            ended: false,
+            sections: false,
+            open_sections: Vec::new(),
+        }
+    }
+
+    ///
+    /// Sets the syntax of the output. Defaults to HTML5.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use squid::BlockParser;
+    /// use squid::html::{OutputMode, Renderer};
+    ///
+    /// let parser = BlockParser::from_string("![squid](squid.png)");
+    /// let mut renderer = Renderer::new(parser).output_mode(OutputMode::Xhtml);
+    ///
+    /// assert_eq!(
+    ///     "<p><img src=\"squid.png\" alt=\"squid\" /></p>",
+    ///     renderer.next().unwrap().unwrap().to_string()
+    /// );
+    /// ```
+    ///
+    pub fn output_mode(mut self, mode: OutputMode) -> Self {
+        self.mode = mode;
+
+        self
+    }
+
+    ///
+    /// Sets how whitespace between elements is written, e.g. to pretty-print the output.
+    /// Defaults to `Formatting::Compact`.
+    ///
+    pub fn formatting(mut self, formatting: Formatting) -> Self {
+        self.formatting = formatting;
+
+        self
+    }
+
+    ///
+    /// Adds a filter transforming the events of every rendered block.
+    ///
+    pub fn filter<T>(mut self, filter: T) -> Self
+    where
+        T: Filter + 'static,
+    {
+        self.filters.push(Box::new(filter));
+
+        self
+    }
+
+    ///
+    /// Adds document metadata (e.g. the title), which is available to the format
+    /// through the `RenderContext`.
+    ///
+    pub fn metadata<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.context.set_metadata(key, value);
+
+        self
+    }
+
+    ///
+    /// Wraps each heading and the following blocks in a `<section>`, which ends
+    /// before the next heading of the same or a higher level. Sections are nested by level.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use squid::BlockParser;
+    /// use squid::html::Renderer;
+    ///
+    /// let parser = BlockParser::from_string("# Squid\n\n## Ink\n\nsepia\n\n# Octopus");
+    /// let output = Renderer::new(parser)
+    ///     .sections(true)
+    ///     .map(|output| output.unwrap().to_string())
+    ///     .collect::<String>();
+    ///
+    /// assert_eq!(
+    ///     "<section id=\"sec-squid\"><h1>Squid</h1><section id=\"sec-ink\"><h2>Ink</h2>\
+    ///      <p>sepia</p></section></section><section id=\"sec-octopus\"><h1>Octopus</h1>\
+    ///      </section>",
+    ///     output
+    /// );
+    /// ```
+    ///
+    pub fn sections(mut self, sections: bool) -> Self {
+        self.sections = sections;
+
+        self
+    }
+
+    ///
+    /// Ends the open sections whose heading level is the same as or deeper than that of
+    /// `heading` (all sections for `None`) and starts a new section for the heading.
+    ///
+    fn render_sections(
+        &mut self,
+        heading: Option<(HeadingLevel, &Text)>,
+    ) -> Result<Output, RenderError> {
+        let mut builder = Builder::new();
+
+        while let Some(&open) = self.open_sections.last() {
+            if heading.is_some_and(|(level, _)| open < level) {
+                break;
+            }
+
+            self.open_sections.pop();
+            builder.unbalanced_end("section");
+        }
+
+        if let Some((level, content)) = heading {
+            let id = self.format.section_id(&mut self.context, content);
+
+            self.open_sections.push(level);
+            builder.unbalanced_start("section", vec![("id".into(), id.into())]);
+        }
+
+        builder.consume()
+    }
+
+    fn finish(&mut self, output: Output) -> Result<Output, RenderError> {
+        let output = if self.filters.is_empty() {
+            output
+        } else {
+            // Sections span several outputs, so they may be started or ended unbalanced
+            let (ends, starts) = output.unbalanced();
+            let events = self.filters
+                .iter_mut()
+                .fold(output.into_events(), |events, filter| {
+                    filter::apply(&mut **filter, events)
+                });
+
+            Output::from_unbalanced_events(events, ends, starts)?
+        };
+
+        Ok(output.with_mode(self.mode).with_formatting(self.formatting))
+    }
+
+    fn render<R>(&mut self, render: R) -> Result<Output, RenderError>
+    where
+        R: FnOnce(&F, &mut Builder, &mut RenderContext),
+    {
+        self.build(render).and_then(|output| self.finish(output))
+    }
+
+    ///
+    /// Like `render`, but without applying the filters.
+    ///
+    fn build<R>(&mut self, render: R) -> Result<Output, RenderError>
+    where
+        R: FnOnce(&F, &mut Builder, &mut RenderContext),
+    {
+        let mut builder = Builder::new();
+
+        render(&self.format, &mut builder, &mut self.context);
+
+        builder.consume()
+    }
+
+    fn render_document_start(&mut self) -> Option<Result<Output, RenderError>> {
+        self.started = true;
+
+        match self.render(|format, builder, context| format.document_start(builder, context)) {
+            Ok(ref output) if output.is_empty() => None,
+            result => Some(result),
+        }
+    }
+
+    ///
+    /// Renders the footnotes and then the end of the document, once the input is exhausted.
+    ///
+    fn render_end(&mut self) -> Option<Result<Output, RenderError>> {
+        if !self.open_sections.is_empty() {
+            return Some(self.render_sections(None).and_then(|output| self.finish(output)));
+        }
+
+        if !self.footnotes.is_empty() {
+            let mut footnotes = mem::take(&mut self.footnotes);
+
+            footnotes.sort_by_key(|footnote| footnote.number);
+
+            return Some(self.render(|format, builder, context| {
+                format.footnotes(builder, context, footnotes)
+            }));
+        }
+
+        if self.ended {
+            return None;
+        }
+
+        self.ended = true;
+
+        match self.render(|format, builder, context| format.document_end(builder, context)) {
+            Ok(ref output) if output.is_empty() => None,
+            result => Some(result),
+        }
     }
 }
 
@@ -104,20 +369,152 @@ where
     type Item = Result<Output, RenderError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let node = self.input.next()?.and_then(|block| {
-            let mut builder = Builder::new();
-
-            match block {
-                Block::Heading(level, content) => self.format.heading(&mut builder, level, content),
-                Block::Paragraph(text) => self.format.paragraph(&mut builder, text),
-                Block::Quote(text) => self.format.quote(&mut builder, text),
-                Block::List(list_type, items) => self.format.list(&mut builder, list_type, items),
-                _ => unimplemented!(),
+        if !self.started {
+            if let Some(output) = self.render_document_start() {
+                return Some(output);
+            }
+        }
+
+        if self.ended {
+            return None;
+        }
+
+        let result = loop {
+            match self.input.next() {
+                Some(Ok(Block::FootnoteDefinition(footnote))) => self.footnotes.push(footnote),
+                // Comments are never rendered, so they don't produce any output
+                Some(Ok(Block::Comment(..))) => (),
+                Some(result) => break result,
+                None => return self.render_end(),
             }
+        };
+
+        Some(result.map_err(RenderError::from).and_then(|block| {
+            let sections = match block {
+                Block::Heading {
+                    level,
+                    ref content,
+                    ..
+                } if self.sections => {
+                    Some(self.render_sections(Some((level, content)))?)
+                }
+                _ => None,
+            };
+
+            let output =
+                self.build(|format, builder, context| format.block(builder, context, block))?;
+
+            self.finish(match sections {
+                Some(sections) => output.prepend(sections),
+                None => output,
+            })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block_parser::BlockParser;
+
+    #[derive(Debug)]
+    struct DocumentFormat;
+
+    impl Format for DocumentFormat {
+        fn document_start(&self, builder: &mut Builder, context: &mut RenderContext) {
+            let title = context.metadata("title").unwrap_or("untitled").to_string();
+
+            builder.tag_start("h1").finish().text(title).tag_end("h1");
+        }
+
+        fn document_end(&self, builder: &mut Builder, context: &mut RenderContext) {
+            let blocks = context.position().to_string();
+
+            builder.tag_start("footer").finish().text(blocks).tag_end("footer");
+        }
+    }
+
+    #[test]
+    fn document_start_and_end_work() {
+        let parser = BlockParser::from_string("squid[^1]\n\nink\n\n[^1]: sepia");
+        let output = Renderer::with_format(DocumentFormat, parser)
+            .metadata("title", "Squids")
+            .map(|output| output.unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(5, output.len());
+        assert_eq!("<h1>Squids</h1>", output[0]);
+        assert_eq!("<footer>2</footer>", output[4]);
+    }
+
+    #[test]
+    fn comments_are_not_rendered() {
+        let parser = BlockParser::from_string("// squid\nink\n/* sepia */").keep_comments(true);
+        let output = Renderer::new(parser)
+            .map(|output| output.unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec!["<p>ink</p>"], output);
+    }
+
+    #[test]
+    fn sections_work() {
+        let parser = BlockParser::from_string(
+            "# Squid Ink!\n\n### Sepia[^1]\n\n## Squid ink\n\n[^1]: brown",
+        );
+        let output = Renderer::new(parser)
+            .sections(true)
+            .map(|output| output.unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                "<section id=\"sec-squid-ink\"><h1>Squid Ink!</h1>",
+                "<section id=\"sec-sepia\"><h3>Sepia<sup id=\"fnref-1\">\
+                 <a href=\"#fn-1\" class=\"footnote-ref\">1</a></sup></h3>",
+                "</section><section id=\"sec-squid-ink-1\"><h2>Squid ink</h2>",
+                "</section></section>",
+            ],
+            &output[..4]
+        );
+        assert!(output[4].starts_with("<section class=\"footnotes\">"));
+    }
+
+    #[test]
+    fn pretty_formatting_separates_blocks() {
+        let parser = BlockParser::from_string("# Title\n\nPara one\n\n- a\n- b\n\nPara two");
+        let output = Renderer::new(parser)
+            .formatting(Formatting::Pretty { indent: 2 })
+            .map(|output| output.unwrap().to_string())
+            .collect::<String>();
+
+        assert_eq!(
+            "<h1>Title</h1>\n<p>Para one</p>\n<ul>\n  <li>a</li>\n  <li>b</li>\n</ul>\n\
+             <p>Para two</p>\n",
+            output
+        );
+    }
 
-            Ok(builder.consume())
-        });
+    #[test]
+    fn labels_dont_collide_with_generated_ids() {
+        let parser = BlockParser::from_string(
+            "[label squid]\n# Squid\n\nInk[^1]\n\n[label fn-1]\n# Squid\n\n[^1]: black",
+        );
+        let output = Renderer::new(parser)
+            .sections(true)
+            .map(|output| output.unwrap().to_string())
+            .collect::<Vec<_>>();
 
-        Some(node.map_err(Into::into))
+        assert_eq!(
+            vec![
+                "<section id=\"sec-squid\"><h1 id=\"squid\">Squid</h1>",
+                "<p>Ink<sup id=\"fnref-1\">\
+                 <a href=\"#fn-1\" class=\"footnote-ref\">1</a></sup></p>",
+                "</section><section id=\"sec-squid-1\"><h1>Squid</h1>",
+                "</section>",
+            ],
+            &output[..4]
+        );
+        assert!(output[4].contains("<li id=\"fn-1\">"));
     }
 }
@@ -0,0 +1,957 @@
+use super::ast::{Inline, Text};
+use super::autolink;
+use super::constants::{CROSS_REFERENCE_PREFIX_TOKEN, ESCAPE_TOKEN, FOOTNOTE_PREFIX_TOKEN,
+                       VARIABLE_PREFIX_TOKEN, VARIABLE_SUFFIX_TOKEN};
+use std::collections::HashMap;
+use std::mem;
+
+const IMAGE_PREFIX_TOKEN: char = '!';
+const LABEL_PREFIX_TOKEN: char = '[';
+const LABEL_SUFFIX_TOKEN: char = ']';
+const DESTINATION_PREFIX_TOKEN: char = '(';
+const DESTINATION_SUFFIX_TOKEN: char = ')';
+const MATH_TOKEN: char = '$';
+
+///
+/// Maps a byte offset in the accumulated text to the line it originates from.
+///
+pub type LineOffset = (usize, usize);
+
+///
+/// A reference link or image whose definition has not been seen yet.
+/// Its target is `None` until the definition is found, which is how
+/// it can be told apart from other links.
+/// Cross-references are left unresolved the same way until their label is found.
+///
+#[derive(Debug, Eq, PartialEq)]
+pub struct UnresolvedReference {
+    pub label: String,
+    /// The original markup, used as a fallback if the reference is never defined.
+    pub source: String,
+    pub line: usize,
+    /// Whether this is a cross-reference, whose labels are separate from reference definitions
+    pub is_cross_reference: bool,
+}
+
+#[derive(Debug)]
+enum Destination<'a> {
+    Target(&'a str),
+    Reference(&'a str),
+}
+
+#[derive(Debug)]
+struct FootnoteState {
+    number: usize,
+    references: usize,
+    /// The line of the first reference, or of the definition if it comes first
+    line: usize,
+    defined: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct InlineParser {
+    definitions: HashMap<String, String>,
+    footnotes: HashMap<String, FootnoteState>,
+    /// The text cross-references to each label are rendered as
+    labels: HashMap<String, String>,
+    unresolved: Vec<UnresolvedReference>,
+    variables: HashMap<String, String>,
+    /// The names of undefined variables since the last call to `take_undefined_variables`,
+    /// with their line
+    undefined_variables: Vec<(String, usize)>,
+    /// Whether placeholders (`{{name}}`) are left as they are instead of being substituted
+    keep_placeholders: bool,
+    autolink: bool,
+}
+
+fn line_at(lines: &[LineOffset], offset: usize) -> usize {
+    lines
+        .iter()
+        .take_while(|&&(start, _)| start <= offset)
+        .last()
+        .map(|&(_, line)| line)
+        .unwrap_or(0)
+}
+
+///
+/// Returns the escaped character if `input` starts with an escape sequence (e.g. `\[`).
+/// Any ASCII punctuation character can be escaped.
+///
+fn escaped(input: &str) -> Option<char> {
+    let mut chars = input.chars();
+
+    if chars.next()? != ESCAPE_TOKEN {
+        return None;
+    }
+
+    chars.next().filter(char::is_ascii_punctuation)
+}
+
+fn unescape(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        match escaped(&input[index..]) {
+            Some(escaped) => {
+                output.push(escaped);
+                chars.next();
+            }
+            None => output.push(c),
+        }
+    }
+
+    output
+}
+
+///
+/// Finds the first occurrence of `needle` that is not escaped.
+///
+fn find_unescaped(input: &str, needle: char) -> Option<usize> {
+    let mut chars = input.char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        if escaped(&input[index..]).is_some() {
+            chars.next();
+        } else if c == needle {
+            return Some(index);
+        }
+    }
+
+    None
+}
+
+///
+/// Finds the unescaped `close` matching the `open` that `input` starts with,
+/// skipping nested pairs (e.g. in `(Squid_(food))`).
+///
+fn find_matching(input: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut chars = input.char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        if escaped(&input[index..]).is_some() {
+            chars.next();
+        } else if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+
+            if depth == 0 {
+                return Some(index);
+            }
+        }
+    }
+
+    None
+}
+
+///
+/// Variable names consist of alphanumerics, `-`, `_` and `.` (e.g. `product.version`).
+///
+pub fn is_variable_name(name: &str) -> bool {
+    !name.is_empty() &&
+        name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+///
+/// Splits `{{name}}rest` into the name and the length of the placeholder.
+/// Whitespace around the name is allowed (e.g. `{{ name }}`).
+///
+fn split_variable(input: &str) -> Option<(&str, usize)> {
+    let rest = input.strip_prefix(VARIABLE_PREFIX_TOKEN)?;
+    let end = rest.find(VARIABLE_SUFFIX_TOKEN)?;
+    let name = rest[..end].trim();
+
+    if is_variable_name(name) {
+        Some((name, VARIABLE_PREFIX_TOKEN.len() + end + VARIABLE_SUFFIX_TOKEN.len()))
+    } else {
+        None
+    }
+}
+
+///
+/// Splits `[label]rest` into the label and the length of the bracketed part.
+/// Brackets may be nested (e.g. an image inside of a link).
+///
+fn split_label(input: &str) -> Option<(&str, usize)> {
+    if !input.starts_with(LABEL_PREFIX_TOKEN) {
+        return None;
+    }
+
+    let mut depth = 0;
+    let mut chars = input.char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        if escaped(&input[index..]).is_some() {
+            chars.next();
+            continue;
+        }
+
+        match c {
+            LABEL_PREFIX_TOKEN => depth += 1,
+            LABEL_SUFFIX_TOKEN => {
+                depth -= 1;
+
+                if depth == 0 {
+                    return Some((&input[1..index], index + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+///
+/// Whether `label` can be referred to by a cross-reference (e.g. `sec:intro` or `fig-1.2`).
+///
+pub fn is_cross_reference_label(label: &str) -> bool {
+    !label.is_empty() &&
+        label
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_.:".contains(c))
+}
+
+///
+/// Parses an inline formula (`$x^2$`). To avoid mistaking prices for formulas,
+/// the dollar signs must not be adjacent to whitespace on the inside
+/// and the closing one must not be followed by a digit.
+///
+fn parse_math(input: &str) -> Option<(Inline, usize)> {
+    let content = &input[MATH_TOKEN.len_utf8()..];
+
+    if content.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let end = find_unescaped(content, MATH_TOKEN)?;
+    let source = &content[..end];
+    let followed_by_digit = content[end + MATH_TOKEN.len_utf8()..].starts_with(|c: char| {
+        c.is_ascii_digit()
+    });
+
+    if source.is_empty() || source.ends_with(char::is_whitespace) || followed_by_digit {
+        return None;
+    }
+
+    Some((Inline::Math(source.into()), end + 2 * MATH_TOKEN.len_utf8()))
+}
+
+///
+/// Parses either an inline target `(url)` or a reference `[label]`.
+/// Returns the destination and its length.
+///
+fn split_destination(input: &str) -> Option<(Destination<'_>, usize)> {
+    let (prefix, suffix) = if input.starts_with(DESTINATION_PREFIX_TOKEN) {
+        (DESTINATION_PREFIX_TOKEN, DESTINATION_SUFFIX_TOKEN)
+    } else if input.starts_with(LABEL_PREFIX_TOKEN) {
+        (LABEL_PREFIX_TOKEN, LABEL_SUFFIX_TOKEN)
+    } else {
+        return None;
+    };
+
+    // Targets can contain parentheses (e.g. `(https://en.wikipedia.org/wiki/Squid_(food))`)
+    let end = if prefix == DESTINATION_PREFIX_TOKEN {
+        find_matching(input, prefix, suffix)?
+    } else {
+        find_unescaped(input, suffix)?
+    };
+    let value = input[1..end].trim();
+
+    if value.is_empty() || value.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let destination = if prefix == DESTINATION_PREFIX_TOKEN {
+        Destination::Target(value)
+    } else {
+        Destination::Reference(value)
+    };
+
+    Some((destination, end + 1))
+}
+
+impl InlineParser {
+    pub fn new() -> Self {
+        InlineParser::default()
+    }
+
+    ///
+    /// Defines the target of a reference.
+    /// Returns `false` if the label was already defined,
+    /// in which case the first definition is kept.
+    ///
+    pub fn define(&mut self, label: &str, target: &str) -> bool {
+        if self.definitions.contains_key(label) {
+            return false;
+        }
+
+        self.definitions.insert(label.into(), target.into());
+
+        true
+    }
+
+    fn footnote(&mut self, label: &str, line: usize) -> &mut FootnoteState {
+        let number = self.footnotes.len() + 1;
+
+        self.footnotes.entry(label.into()).or_insert(FootnoteState {
+            number,
+            references: 0,
+            line,
+            defined: false,
+        })
+    }
+
+    ///
+    /// Marks the footnote `label` as defined and returns its number.
+    /// Returns `None` if the footnote was already defined.
+    ///
+    pub fn define_footnote(&mut self, label: &str, line: usize) -> Option<usize> {
+        let footnote = self.footnote(label, line);
+
+        if footnote.defined {
+            return None;
+        }
+
+        footnote.defined = true;
+
+        Some(footnote.number)
+    }
+
+    ///
+    /// Whether the footnote `label` has been referenced so far.
+    ///
+    pub fn is_footnote_referenced(&self, label: &str) -> bool {
+        self.footnotes
+            .get(label)
+            .is_some_and(|footnote| footnote.references > 0)
+    }
+
+    ///
+    /// Returns the labels of all referenced footnotes that were never defined,
+    /// with the line of their first reference.
+    ///
+    pub fn undefined_footnotes(&self) -> Vec<(String, usize)> {
+        self.footnotes_where(|footnote| !footnote.defined)
+    }
+
+    ///
+    /// Returns the labels of all defined footnotes that were never referenced,
+    /// with the line of their definition.
+    ///
+    pub fn unreferenced_footnotes(&self) -> Vec<(String, usize)> {
+        self.footnotes_where(|footnote| footnote.references == 0)
+    }
+
+    fn footnotes_where<P>(&self, predicate: P) -> Vec<(String, usize)>
+    where
+        P: Fn(&FootnoteState) -> bool,
+    {
+        let mut footnotes = self.footnotes
+            .iter()
+            .filter(|&(_, footnote)| predicate(footnote))
+            .map(|(label, footnote)| (footnote.number, label.clone(), footnote.line))
+            .collect::<Vec<_>>();
+
+        footnotes.sort();
+        footnotes.into_iter().map(|(_, label, line)| (label, line)).collect()
+    }
+
+    ///
+    /// Defines the text cross-references to `label` are rendered as.
+    /// Returns `false` if the label is already defined.
+    ///
+    pub fn define_label(&mut self, label: &str, text: &str) -> bool {
+        if self.labels.contains_key(label) {
+            return false;
+        }
+
+        self.labels.insert(label.into(), text.into());
+
+        true
+    }
+
+    ///
+    /// Defines the value placeholders for `name` are substituted with,
+    /// replacing any previous value.
+    ///
+    pub fn define_variable(&mut self, name: &str, value: &str) {
+        self.variables.insert(name.into(), value.into());
+    }
+
+    ///
+    /// Returns the placeholders for undefined variables since the last call,
+    /// with the line they are on.
+    ///
+    pub fn take_undefined_variables(&mut self) -> Vec<(String, usize)> {
+        mem::take(&mut self.undefined_variables)
+    }
+
+    pub fn set_keep_placeholders(&mut self, enabled: bool) {
+        self.keep_placeholders = enabled;
+    }
+
+    ///
+    /// Returns the references that could not be resolved since the last call,
+    /// in the order they are visited by `pending::visit_unresolved_mut`.
+    /// Must be called after each block.
+    ///
+    pub fn take_unresolved(&mut self) -> Vec<UnresolvedReference> {
+        mem::take(&mut self.unresolved)
+    }
+
+    pub fn set_autolink(&mut self, enabled: bool) {
+        self.autolink = enabled;
+    }
+
+    pub fn parse(&mut self, input: &str, lines: &[LineOffset]) -> Text {
+        let text = self.parse_inlines(input, 0, lines);
+
+        if self.autolink {
+            autolink::autolink(text)
+        } else {
+            text
+        }
+    }
+
+    fn parse_inlines(&mut self, input: &str, offset: usize, lines: &[LineOffset]) -> Text {
+        let mut text = Vec::new();
+        let mut chunk = String::new();
+        let mut position = 0;
+
+        while position < input.len() {
+            let rest = &input[position..];
+
+            if let Some(c) = escaped(rest) {
+                chunk.push(c);
+                position += ESCAPE_TOKEN.len_utf8() + c.len_utf8();
+
+                continue;
+            }
+
+            let variable = self.substitute_variable(rest, offset + position, lines);
+
+            if let Some((value, length)) = variable {
+                // Values are text, they are not parsed themselves
+                chunk.push_str(&value);
+                position += length;
+
+                continue;
+            }
+
+            if let Some((inline, length)) = self.parse_element(rest, offset + position, lines) {
+                if !chunk.is_empty() {
+                    text.push(Inline::Chunk(mem::take(&mut chunk)));
+                }
+
+                text.push(inline);
+                position += length;
+
+                continue;
+            }
+
+            // unwrapping here is safe, as `rest` is not empty
+            let c = rest.chars().next().unwrap();
+
+            chunk.push(c);
+            position += c.len_utf8();
+        }
+
+        if !chunk.is_empty() {
+            text.push(Inline::Chunk(chunk));
+        }
+
+        text
+    }
+
+    ///
+    /// Returns the value replacing the placeholder at the start of `input`
+    /// and the length of the placeholder.
+    /// Placeholders for undefined variables are kept and reported.
+    ///
+    fn substitute_variable(
+        &mut self,
+        input: &str,
+        offset: usize,
+        lines: &[LineOffset],
+    ) -> Option<(String, usize)> {
+        if self.keep_placeholders {
+            return None;
+        }
+
+        let (name, length) = split_variable(input)?;
+
+        match self.variables.get(name) {
+            Some(value) => Some((value.clone(), length)),
+            None => {
+                self.undefined_variables.push((name.into(), line_at(lines, offset)));
+
+                Some((input[..length].into(), length))
+            }
+        }
+    }
+
+    ///
+    /// Unescapes a link or image destination and replaces its placeholders
+    /// (e.g. `{{base}}/download`).
+    ///
+    fn parse_target(&mut self, input: &str, offset: usize, lines: &[LineOffset]) -> String {
+        let mut target = String::with_capacity(input.len());
+        let mut position = 0;
+
+        while position < input.len() {
+            let rest = &input[position..];
+
+            if let Some(c) = escaped(rest) {
+                target.push(c);
+                position += ESCAPE_TOKEN.len_utf8() + c.len_utf8();
+            } else if let Some((value, length)) = self.substitute_variable(rest, offset, lines) {
+                target.push_str(&value);
+                position += length;
+            } else {
+                // unwrapping here is safe, as `rest` is not empty
+                let c = rest.chars().next().unwrap();
+
+                target.push(c);
+                position += c.len_utf8();
+            }
+        }
+
+        target
+    }
+
+    fn parse_element(
+        &mut self,
+        input: &str,
+        offset: usize,
+        lines: &[LineOffset],
+    ) -> Option<(Inline, usize)> {
+        if input.starts_with(MATH_TOKEN) {
+            return parse_math(input);
+        }
+
+        if input.starts_with(FOOTNOTE_PREFIX_TOKEN) {
+            return self.parse_footnote_reference(input, offset, lines);
+        }
+
+        if input.starts_with(CROSS_REFERENCE_PREFIX_TOKEN) {
+            if let Some(result) = self.parse_cross_reference(input, offset, lines) {
+                return Some(result);
+            }
+        }
+
+        if input.starts_with(IMAGE_PREFIX_TOKEN) {
+            let (alt, label_length) = split_label(&input[1..])?;
+            let (destination, destination_length) =
+                split_destination(&input[1 + label_length..])?;
+            let length = 1 + label_length + destination_length;
+            let source = self.target(destination, &input[..length], offset, lines);
+
+            return Some((
+                Inline::Image {
+                    source,
+                    alt: unescape(alt),
+                },
+                length,
+            ));
+        }
+
+        let (label, label_length) = split_label(input)?;
+        let (destination, destination_length) = split_destination(&input[label_length..])?;
+        let length = label_length + destination_length;
+        // Content is parsed first, so that unresolved references in nested images
+        // are recorded before the link itself
+        let content = self.parse_inlines(label, offset + 1, lines);
+        let target = self.target(destination, &input[..length], offset, lines);
+
+        Some((Inline::Link { target, content }, length))
+    }
+
+    fn parse_footnote_reference(
+        &mut self,
+        input: &str,
+        offset: usize,
+        lines: &[LineOffset],
+    ) -> Option<(Inline, usize)> {
+        let end = find_unescaped(input, LABEL_SUFFIX_TOKEN)?;
+        let label = &input[FOOTNOTE_PREFIX_TOKEN.len()..end];
+
+        let is_invalid = |c: char| c.is_whitespace() || c == LABEL_PREFIX_TOKEN;
+
+        if label.is_empty() || label.contains(is_invalid) {
+            return None;
+        }
+
+        let label = unescape(label);
+        let footnote = self.footnote(&label, line_at(lines, offset));
+
+        footnote.references += 1;
+
+        Some((
+            Inline::FootnoteRef {
+                number: footnote.number,
+                occurrence: footnote.references,
+                label,
+            },
+            end + 1,
+        ))
+    }
+
+    fn parse_cross_reference(
+        &mut self,
+        input: &str,
+        offset: usize,
+        lines: &[LineOffset],
+    ) -> Option<(Inline, usize)> {
+        let end = input.find(LABEL_SUFFIX_TOKEN)?;
+        let label = &input[CROSS_REFERENCE_PREFIX_TOKEN.len()..end];
+
+        if !is_cross_reference_label(label) {
+            return None;
+        }
+
+        let rest = &input[end + 1..];
+
+        // `[@label](…)` and `[@label][…]` are links
+        if rest.starts_with(DESTINATION_PREFIX_TOKEN) || rest.starts_with(LABEL_PREFIX_TOKEN) {
+            return None;
+        }
+
+        let text = match self.labels.get(label) {
+            Some(text) => Some(text.clone()),
+            None => {
+                self.unresolved.push(UnresolvedReference {
+                    label: label.into(),
+                    source: input[..end + 1].into(),
+                    line: line_at(lines, offset),
+                    is_cross_reference: true,
+                });
+
+                None
+            }
+        };
+
+        Some((
+            Inline::CrossRef {
+                label: label.into(),
+                text,
+            },
+            end + 1,
+        ))
+    }
+
+    fn target(
+        &mut self,
+        destination: Destination,
+        source: &str,
+        offset: usize,
+        lines: &[LineOffset],
+    ) -> Option<String> {
+        match destination {
+            Destination::Target(target) => Some(self.parse_target(target, offset, lines)),
+            Destination::Reference(label) => {
+                if let Some(target) = self.definitions.get(label) {
+                    return Some(target.clone());
+                }
+
+                self.unresolved.push(UnresolvedReference {
+                    label: label.into(),
+                    source: source.into(),
+                    line: line_at(lines, offset),
+                    is_cross_reference: false,
+                });
+
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(value: &str) -> Inline {
+        Inline::Chunk(value.into())
+    }
+
+    #[test]
+    fn links_work() {
+        let mut parser = InlineParser::new();
+
+        assert_eq!(
+            vec![
+                chunk("see "),
+                Inline::Link {
+                    target: Some("https://example.com".into()),
+                    content: vec![chunk("the example")],
+                },
+                chunk("."),
+            ],
+            parser.parse("see [the example](https://example.com).", &[])
+        );
+    }
+
+    #[test]
+    fn images_work() {
+        let mut parser = InlineParser::new();
+
+        assert_eq!(
+            vec![
+                Inline::Link {
+                    target: Some("/squid".into()),
+                    content: vec![
+                        Inline::Image {
+                            source: Some("squid.png".into()),
+                            alt: "a squid".into(),
+                        },
+                    ],
+                },
+            ],
+            parser.parse("[![a squid](squid.png)](/squid)", &[])
+        );
+    }
+
+    #[test]
+    fn invalid_links_are_text() {
+        let mut parser = InlineParser::new();
+
+        assert_eq!(
+            vec![chunk("[foo] (bar) [foo](a b) ![foo] [foo]( )")],
+            parser.parse("[foo] (bar) [foo](a b) ![foo] [foo]( )", &[])
+        );
+    }
+
+    #[test]
+    fn escapes_work() {
+        let mut parser = InlineParser::new();
+
+        assert_eq!(
+            vec![
+                chunk("# [not a link](foo) \\ \\a "),
+                Inline::Link {
+                    target: Some("a)b".into()),
+                    content: vec![chunk("a ] b")],
+                },
+                chunk("\\"),
+            ],
+            parser.parse("\\# \\[not a link](foo) \\\\ \\a [a \\] b](a\\)b)\\", &[])
+        );
+    }
+
+    #[test]
+    fn math_works() {
+        let mut parser = InlineParser::new();
+
+        assert_eq!(
+            vec![
+                chunk("if "),
+                Inline::Math("x^2 \\$ 1".into()),
+                chunk(" then $5 or $ x$ and $10"),
+            ],
+            parser.parse("if $x^2 \\$ 1$ then $5 or $ x$ and $10", &[])
+        );
+    }
+
+    #[test]
+    fn references_work() {
+        let mut parser = InlineParser::new();
+
+        parser.define("squid", "https://squid.rs");
+
+        assert_eq!(
+            vec![
+                Inline::Link {
+                    target: Some("https://squid.rs".into()),
+                    content: vec![chunk("Squid")],
+                },
+                chunk(" "),
+                Inline::Link {
+                    target: None,
+                    content: vec![chunk("Ink")],
+                },
+            ],
+            parser.parse("[Squid][squid] [Ink][ink]", &[(0, 3), (15, 4)])
+        );
+
+        assert_eq!(
+            vec![
+                UnresolvedReference {
+                    label: "ink".into(),
+                    source: "[Ink][ink]".into(),
+                    line: 4,
+                    is_cross_reference: false,
+                },
+            ],
+            parser.take_unresolved()
+        );
+    }
+
+    #[test]
+    fn cross_references_work() {
+        let mut parser = InlineParser::new();
+
+        assert!(parser.define_label("intro", "1.2"));
+        assert!(!parser.define_label("intro", "3"));
+
+        assert_eq!(
+            vec![
+                chunk("see "),
+                Inline::CrossRef {
+                    label: "intro".into(),
+                    text: Some("1.2".into()),
+                },
+                chunk(" and "),
+                Inline::CrossRef {
+                    label: "later".into(),
+                    text: None,
+                },
+                chunk(" [@not a label] "),
+                Inline::Link {
+                    target: Some("/".into()),
+                    content: vec![chunk("@home")],
+                },
+            ],
+            parser.parse("see [@intro] and [@later] [@not a label] [@home](/)", &[(0, 1)])
+        );
+
+        assert_eq!(
+            vec![
+                UnresolvedReference {
+                    label: "later".into(),
+                    source: "[@later]".into(),
+                    line: 1,
+                    is_cross_reference: true,
+                },
+            ],
+            parser.take_unresolved()
+        );
+    }
+
+    #[test]
+    fn footnote_references_work() {
+        let mut parser = InlineParser::new();
+
+        assert_eq!(Some(1), parser.define_footnote("b", 1));
+
+        assert_eq!(
+            vec![
+                chunk("a"),
+                Inline::FootnoteRef {
+                    label: "a".into(),
+                    number: 2,
+                    occurrence: 1,
+                },
+                Inline::FootnoteRef {
+                    label: "b".into(),
+                    number: 1,
+                    occurrence: 1,
+                },
+                chunk(" [^ a] "),
+                Inline::FootnoteRef {
+                    label: "a".into(),
+                    number: 2,
+                    occurrence: 2,
+                },
+            ],
+            parser.parse("a[^a][^b] [^ a] [^a]", &[(0, 3)])
+        );
+
+        assert_eq!(None, parser.define_footnote("b", 5));
+        assert_eq!(vec![("a".to_string(), 3)], parser.undefined_footnotes());
+    }
+
+    #[test]
+    fn duplicate_definitions_are_rejected() {
+        let mut parser = InlineParser::new();
+
+        assert!(parser.define("squid", "https://squid.rs"));
+        assert!(!parser.define("squid", "https://example.com"));
+    }
+
+    #[test]
+    fn variables_work() {
+        let mut parser = InlineParser::new();
+
+        parser.define_variable("product", "*Squid*");
+
+        assert_eq!(
+            vec![chunk("Welcome to *Squid* {{ version }}, {{product} and {{product}}.")],
+            parser.parse(
+                "Welcome to {{ product }} {{ version }}, {{product} and \\{{product}}.",
+                &[(0, 1)],
+            )
+        );
+        assert_eq!(vec![("version".into(), 1)], parser.take_undefined_variables());
+
+        parser.set_keep_placeholders(true);
+
+        assert_eq!(
+            vec![
+                Inline::Link {
+                    target: Some("/".into()),
+                    content: vec![chunk("{{product}}")],
+                },
+            ],
+            parser.parse("[{{product}}](/)", &[(0, 1)])
+        );
+        assert!(parser.take_undefined_variables().is_empty());
+    }
+
+    #[test]
+    fn variables_in_destinations_work() {
+        let mut parser = InlineParser::new();
+
+        parser.define_variable("base", "https://squid.rs");
+
+        assert_eq!(
+            vec![
+                Inline::Link {
+                    target: Some("https://squid.rs/dl".into()),
+                    content: vec![chunk("Download")],
+                },
+                chunk(" "),
+                Inline::Image {
+                    source: Some("{{host}}/ink.png".into()),
+                    alt: "ink".into(),
+                },
+                chunk(" "),
+                Inline::Link {
+                    target: Some("{{base}}".into()),
+                    content: vec![chunk("escaped")],
+                },
+            ],
+            parser.parse(
+                "[Download]({{base}}/dl) ![ink]({{host}}/ink.png) [escaped](\\{{base}})",
+                &[(0, 1)],
+            )
+        );
+        assert_eq!(vec![("host".into(), 1)], parser.take_undefined_variables());
+    }
+
+    #[test]
+    fn parentheses_in_targets_work() {
+        let mut parser = InlineParser::new();
+
+        assert_eq!(
+            vec![
+                Inline::Link {
+                    target: Some("https://en.wikipedia.org/wiki/Squid_(food)".into()),
+                    content: vec![chunk("x")],
+                },
+                chunk(" "),
+                Inline::Link {
+                    target: Some("a(".into()),
+                    content: vec![chunk("y")],
+                },
+                chunk(" (z)"),
+            ],
+            parser.parse(
+                "[x](https://en.wikipedia.org/wiki/Squid_(food)) [y](a\\() (z)",
+                &[(0, 1)],
+            )
+        );
+    }
+}
@@ -1,12 +1,18 @@
 #![feature(non_exhaustive)]
 
+mod autolink;
 mod block_tokenizer;
 mod block_parser;
+mod checkbox;
+mod inline_parser;
+mod pending;
 mod constants;
 mod tokens;
 mod input;
 pub mod ast;
 pub mod error;
 pub mod html;
+pub mod resolver;
 
 pub use block_parser::BlockParser;
+pub use checkbox::{toggle_checkbox, toggle_checkbox_in};
@@ -0,0 +1,238 @@
+use super::ast::{Block, Inline, Text};
+use super::error::ParseError;
+use super::inline_parser::UnresolvedReference;
+use std::collections::VecDeque;
+use std::mem;
+
+#[derive(Debug)]
+struct Pending {
+    result: Result<Block, ParseError>,
+    unresolved: Vec<UnresolvedReference>,
+    /// The label of a footnote definition whose footnote hasn't been referenced yet
+    unreferenced: Option<String>,
+}
+
+///
+/// Blocks referencing definitions that haven't been seen yet are held back
+/// until the definition is found (or the input ends).
+/// Footnote definitions are held back the same way until their footnote is referenced.
+/// Blocks following them are held back too, to keep them in order.
+///
+#[derive(Debug, Default)]
+pub struct PendingBlocks {
+    queue: VecDeque<Pending>,
+}
+
+fn is_unresolved(inline: &Inline) -> bool {
+    match *inline {
+        Inline::Link { ref target, .. } => target.is_none(),
+        Inline::Image { ref source, .. } => source.is_none(),
+        Inline::CrossRef { ref text, .. } => text.is_none(),
+        _ => false,
+    }
+}
+
+fn visit_text_unresolved_mut<F>(text: &mut Text, visitor: &mut F)
+where
+    F: FnMut(&mut Inline),
+{
+    for inline in text.iter_mut() {
+        // Links are visited after their content, matching the order
+        // in which the inline parser records unresolved references
+        if let Inline::Link { ref mut content, .. } = *inline {
+            visit_text_unresolved_mut(content, visitor);
+        }
+
+        if is_unresolved(inline) {
+            visitor(inline);
+        }
+    }
+}
+
+///
+/// Visits all links and images of a block that are waiting for their definition.
+///
+pub fn visit_unresolved_mut<F>(block: &mut Block, mut visitor: F)
+where
+    F: FnMut(&mut Inline),
+{
+    visit_block_unresolved_mut(block, &mut visitor);
+}
+
+fn visit_block_unresolved_mut<F>(block: &mut Block, visitor: &mut F)
+where
+    F: FnMut(&mut Inline),
+{
+    match *block {
+        Block::Heading {
+            content: ref mut text,
+            ..
+        } |
+        Block::Paragraph(ref mut text) |
+        Block::Quote(ref mut text) => visit_text_unresolved_mut(text, visitor),
+        Block::List(_, ref mut items) => {
+            for item in items {
+                visit_text_unresolved_mut(&mut item.content, visitor);
+            }
+        }
+        Block::FootnoteDefinition(ref mut footnote) => {
+            visit_text_unresolved_mut(&mut footnote.content, visitor)
+        }
+        Block::DefinitionList(ref mut entries) => {
+            for &mut (ref mut term, ref mut definitions) in entries {
+                visit_text_unresolved_mut(term, visitor);
+
+                for definition in definitions {
+                    visit_block_unresolved_mut(definition, visitor);
+                }
+            }
+        }
+        Block::Figure {
+            ref mut caption,
+            ref mut body,
+            ..
+        } => {
+            // The caption is parsed before the body
+            visit_text_unresolved_mut(caption, visitor);
+            visit_block_unresolved_mut(body, visitor);
+        }
+        Block::Preformatted(..) | Block::Math(..) | Block::Comment(..) => {}
+    }
+}
+
+fn set_target(inline: &mut Inline, value: &str) {
+    match *inline {
+        Inline::Link { ref mut target, .. } => *target = Some(value.into()),
+        Inline::Image { ref mut source, .. } => *source = Some(value.into()),
+        Inline::CrossRef { ref mut text, .. } => *text = Some(value.into()),
+        _ => {}
+    }
+}
+
+impl PendingBlocks {
+    pub fn new() -> Self {
+        PendingBlocks::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn push(
+        &mut self,
+        result: Result<Block, ParseError>,
+        unresolved: Vec<UnresolvedReference>,
+    ) {
+        let unreferenced = match result {
+            Ok(Block::FootnoteDefinition(ref footnote)) if !footnote.referenced => {
+                Some(footnote.label.clone())
+            }
+            _ => None,
+        };
+
+        self.queue.push_back(Pending {
+            result,
+            unresolved,
+            unreferenced,
+        });
+    }
+
+    ///
+    /// Marks the pending footnote definitions as referenced if `is_referenced` returns `true`
+    /// for their label.
+    ///
+    pub fn reference_footnotes<P>(&mut self, is_referenced: P)
+    where
+        P: Fn(&str) -> bool,
+    {
+        for pending in &mut self.queue {
+            if !pending.unreferenced.as_deref().is_some_and(&is_referenced) {
+                continue;
+            }
+
+            pending.unreferenced = None;
+
+            if let Ok(Block::FootnoteDefinition(ref mut footnote)) = pending.result {
+                footnote.referenced = true;
+            }
+        }
+    }
+
+    ///
+    /// Resolves all pending references to the reference definition `label`.
+    ///
+    pub fn define(&mut self, label: &str, target: &str) {
+        self.resolve(label, false, target);
+    }
+
+    ///
+    /// Resolves all pending cross-references to the block labeled `label`.
+    ///
+    pub fn define_label(&mut self, label: &str, text: &str) {
+        self.resolve(label, true, text);
+    }
+
+    fn resolve(&mut self, label: &str, is_cross_reference: bool, value: &str) {
+        let matches = |reference: &UnresolvedReference| {
+            reference.label == label && reference.is_cross_reference == is_cross_reference
+        };
+
+        for pending in &mut self.queue {
+            // Unresolved references are in the same order as they are visited
+            let mut references = pending.unresolved.iter();
+
+            if let Ok(ref mut block) = pending.result {
+                visit_unresolved_mut(block, |inline| {
+                    if references.next().is_some_and(matches) {
+                        set_target(inline, value);
+                    }
+                });
+            }
+
+            pending.unresolved.retain(|reference| !matches(reference));
+        }
+    }
+
+    ///
+    /// Returns the first block, if it has no unresolved references left
+    /// and isn't waiting for a reference to its footnote.
+    ///
+    pub fn pop_resolved(&mut self) -> Option<Result<Block, ParseError>> {
+        let front = self.queue.front()?;
+
+        if !front.unresolved.is_empty() || front.unreferenced.is_some() {
+            return None;
+        }
+
+        self.queue.pop_front().map(|pending| pending.result)
+    }
+
+    ///
+    /// Gives up on all remaining references, which are replaced by their original markup,
+    /// and leaves the footnotes that are still unreferenced unreferenced.
+    /// Returns the references that were never defined.
+    ///
+    pub fn finish(&mut self) -> Vec<UnresolvedReference> {
+        let mut undefined = Vec::new();
+
+        for pending in &mut self.queue {
+            let unresolved = mem::take(&mut pending.unresolved);
+
+            pending.unreferenced = None;
+
+            let mut references = unresolved.iter();
+
+            if let Ok(ref mut block) = pending.result {
+                visit_unresolved_mut(block, |inline| {
+                    if let Some(reference) = references.next() {
+                        *inline = Inline::Chunk(reference.source.clone());
+                    }
+                });
+            }
+
+            undefined.extend(unresolved);
+        }
+
+        undefined
+    }
+}
@@ -0,0 +1,191 @@
+//!
+//! Loading the files included with `[include <path>]`.
+//!
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+///
+/// Loads included files for a `BlockParser`.
+///
+pub trait Resolver: Debug {
+    ///
+    /// Returns the name and the content of the file `path` refers to.
+    /// `parent` is the name of the including file, or `None` for the document itself.
+    ///
+    /// Names identify files, e.g. to detect files that include themselves,
+    /// and are shown in diagnostics.
+    ///
+    fn resolve(&self, path: &str, parent: Option<&str>) -> io::Result<(String, String)>;
+}
+
+///
+/// Loads files from the file system. Paths are relative to the including file,
+/// or to the root directory for the document itself.
+/// Files outside of the root directory (e.g. `/etc/passwd` or `../../secret`) can't be included,
+/// even through symbolic links.
+///
+#[derive(Debug)]
+pub struct FileResolver {
+    root: PathBuf,
+}
+
+///
+/// Loads files from memory, which is useful for tests.
+/// Paths are relative to the including file, like with `FileResolver`.
+///
+/// # Example
+///
+/// ```
+/// use squid::BlockParser;
+/// use squid::resolver::MemoryResolver;
+///
+/// let resolver = MemoryResolver::new()
+///     .file("intro.sq", "# Intro")
+///     .file("chapters/squid.sq", "# Squid");
+///
+/// let parser = BlockParser::from_string("[include intro.sq]").resolver(resolver);
+/// ```
+///
+#[derive(Debug, Default)]
+pub struct MemoryResolver {
+    files: HashMap<String, String>,
+}
+
+///
+/// Joins `path` to the directory of `parent`, removing `.` and `..` components.
+///
+fn join(parent: Option<&str>, path: &str) -> PathBuf {
+    let directory = parent.and_then(|parent| Path::new(parent).parent());
+    let mut joined = PathBuf::new();
+
+    for component in directory.unwrap_or_else(|| Path::new("")).join(path).components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                joined.pop();
+            }
+            component => joined.push(component),
+        }
+    }
+
+    joined
+}
+
+impl FileResolver {
+    pub fn new<P>(root: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        FileResolver { root: root.into() }
+    }
+}
+
+impl Resolver for FileResolver {
+    fn resolve(&self, path: &str, parent: Option<&str>) -> io::Result<(String, String)> {
+        let path = match parent {
+            Some(..) => join(parent, path),
+            None => self.root.join(path),
+        };
+
+        // Canonical paths keep the names of the same file identical
+        let path = fs::canonicalize(path)?;
+
+        if !path.starts_with(fs::canonicalize(&self.root)?) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the file is outside of the root directory",
+            ));
+        }
+
+        let content = fs::read_to_string(&path)?;
+
+        Ok((path.to_string_lossy().into_owned(), content))
+    }
+}
+
+impl MemoryResolver {
+    pub fn new() -> Self {
+        MemoryResolver::default()
+    }
+
+    pub fn file<N, C>(mut self, name: N, content: C) -> Self
+    where
+        N: Into<String>,
+        C: Into<String>,
+    {
+        self.files.insert(name.into(), content.into());
+
+        self
+    }
+}
+
+impl Resolver for MemoryResolver {
+    fn resolve(&self, path: &str, parent: Option<&str>) -> io::Result<(String, String)> {
+        let name = join(parent, path).to_string_lossy().into_owned();
+
+        match self.files.get(&name) {
+            Some(content) => Ok((name, content.clone())),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "file not found")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_resolver_works() {
+        let resolver = MemoryResolver::new()
+            .file("squid.sq", "squid")
+            .file("arms/ink.sq", "ink");
+
+        assert_eq!(
+            ("arms/ink.sq".into(), "ink".into()),
+            resolver.resolve("arms/ink.sq", None).unwrap()
+        );
+        assert_eq!(
+            ("squid.sq".into(), "squid".into()),
+            resolver.resolve("../squid.sq", Some("arms/ink.sq")).unwrap()
+        );
+        assert_eq!(
+            ("arms/ink.sq".into(), "ink".into()),
+            resolver.resolve("./ink.sq", Some("arms/tentacles.sq")).unwrap()
+        );
+        assert!(resolver.resolve("ink.sq", None).is_err());
+    }
+
+    #[test]
+    fn file_resolver_is_confined_to_the_root() {
+        let directory = std::env::temp_dir().join(format!("squid-{}", std::process::id()));
+        let root = directory.join("root");
+
+        fs::create_dir_all(root.join("arms")).unwrap();
+        fs::write(root.join("arms/ink.sq"), "ink").unwrap();
+        fs::write(directory.join("secret.sq"), "secret").unwrap();
+
+        let resolver = FileResolver::new(&root);
+        let (name, content) = resolver.resolve("arms/ink.sq", None).unwrap();
+
+        assert_eq!("ink", content);
+        assert!(resolver.resolve("../arms/./ink.sq", Some(&name)).is_ok());
+
+        let secret = directory.join("secret.sq");
+
+        for &(path, parent) in &[
+            ("../../secret.sq", Some(name.as_str())),
+            ("../secret.sq", None),
+            (&*secret.to_string_lossy(), None),
+        ] {
+            let err = resolver.resolve(path, parent).unwrap_err();
+
+            assert_eq!(io::ErrorKind::PermissionDenied, err.kind());
+        }
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
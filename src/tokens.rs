@@ -7,39 +7,60 @@ pub enum Line<'a> {
     Heading1(Cow<'a, str>),
     Heading2(Cow<'a, str>),
     Heading3(Cow<'a, str>),
+    Heading4(Cow<'a, str>),
+    Heading5(Cow<'a, str>),
+    Heading6(Cow<'a, str>),
     Text(Cow<'a, str>),
     Quote(Cow<'a, str>),
     Decorator(Cow<'a, str>),
     UnorderedList(Cow<'a, str>),
-    OrderedList(Cow<'a, str>),
+    /// Contains the explicit number of the item (if any) and its value
+    OrderedList(Option<u32>, Cow<'a, str>),
+    /// A definition of the preceding term in a definition list
+    Definition(Cow<'a, str>),
+    /// Contains the label and the target of the reference
+    ReferenceDefinition(Cow<'a, str>, Cow<'a, str>),
+    /// Contains the label and the (first line of the) content of the footnote
+    FootnoteDefinition(Cow<'a, str>, Cow<'a, str>),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum LineType {
     Blank,
     Divider,
     Heading1,
     Heading2,
     Heading3,
+    Heading4,
+    Heading5,
+    Heading6,
     Text,
     Quote,
     Decorator,
     UnorderedList,
     OrderedList,
+    Definition,
+    ReferenceDefinition,
+    FootnoteDefinition,
 }
 
 impl<'a> Line<'a> {
     pub fn value(self) -> Option<Cow<'a, str>> {
         match self {
-            Line::Blank | Line::Divider => None,
+            Line::Blank | Line::Divider | Line::ReferenceDefinition(..) => None,
             Line::Heading1(value) |
             Line::Heading2(value) |
             Line::Heading3(value) |
+            Line::Heading4(value) |
+            Line::Heading5(value) |
+            Line::Heading6(value) |
             Line::Text(value) |
             Line::Quote(value) |
             Line::Decorator(value) |
             Line::UnorderedList(value) |
-            Line::OrderedList(value) => Some(value),
+            Line::OrderedList(_, value) |
+            Line::Definition(value) |
+            Line::FootnoteDefinition(_, value) => Some(value),
         }
     }
 }